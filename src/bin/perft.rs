@@ -5,19 +5,32 @@ use rustypawn::Game;
 use rustypawn::millis_since;
 
 fn perft_sub(game: &mut Game, depth: usize) -> usize {
-    if depth == 0 {
-        return 1;
-    }
+    let move_list = game.legal_moves();
 
-    let move_list = game.generate_moves();
+    if depth == 1 {
+        return move_list.len();
+    }
 
     let mut result = 0;
     for mv in move_list {
-        if game.make_move(mv) {
-            let count_sub = perft_sub(game, depth - 1);
-            game.unmake_move(mv);
-            result += count_sub;
+        game.make_move(mv);
+        result += perft_sub(game, depth - 1);
+        game.unmake_move(mv);
+    }
+
+    result
+}
+
+// The same count the way the search walks the tree: pseudo-legal moves,
+// with make_move rejecting those that leave the king in check.
+fn perft_pseudo_legal_sub(game: &mut Game, depth: usize) -> usize {
+    let mut result = 0;
+    for mv in game.generate_moves() {
+        if !game.make_move(mv) {
+            continue;
         }
+        result += if depth == 1 { 1 } else { perft_pseudo_legal_sub(game, depth - 1) };
+        game.unmake_move(mv);
     }
 
     result
@@ -25,14 +38,41 @@ fn perft_sub(game: &mut Game, depth: usize) -> usize {
 
 pub fn perft(fen: &str, depth: usize) -> usize {
     match Game::from_fen(fen) {
+        Ok(_) if depth == 0 => 1,
         Ok(mut game) => perft_sub(&mut game, depth),
         Err(_) => 0
     }
 }
 
+pub fn perft_pseudo_legal(fen: &str, depth: usize) -> usize {
+    match Game::from_fen(fen) {
+        Ok(_) if depth == 0 => 1,
+        Ok(mut game) => perft_pseudo_legal_sub(&mut game, depth),
+        Err(_) => 0
+    }
+}
+
+fn run_perft(name: &str, fen: &str, depth: usize, verification: usize) {
+    let count = perft(fen, depth);
+    assert_eq!(count, verification);
+    println!("{} {}", name, count);
+}
+
+
+fn main() {
+    let start = Instant::now();
+
+    run_perft("Initial position", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0", 5, 4865609);
+    run_perft("Kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -", 4, 4085603);
+    run_perft("Position 3", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -", 6, 11030083);
+    run_perft("Position 6", "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", 4, 3894594);
+
+    println!("Time: {} ms", millis_since(&start));
+}
+
 #[cfg(test)]
 mod tests {
-    use super::perft;
+    use super::{perft, perft_pseudo_legal};
 
     #[test]
     fn perft_initial_position() {
@@ -95,22 +135,13 @@ mod tests {
         assert_eq!(perft(fen, 3), 89890);
     }
 
-}
-
-fn run_perft(name: &str, fen: &str, depth: usize, verification: usize) {
-    let count = perft(fen, depth);
-    assert_eq!(count, verification);
-    println!("{} {}", name, count);
-}
-
-
-fn main() {
-    let start = Instant::now();
-
-    run_perft("Initial position", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0", 5, 4865609);
-    run_perft("Kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -", 4, 4085603);
-    run_perft("Position 3", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -", 6, 11030083);
-    run_perft("Position 6", "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", 4, 3894594);
+    #[test]
+    fn perft_pseudo_legal_moves() {
+        assert_eq!(perft_pseudo_legal("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0", 4), 197281);
+        assert_eq!(perft_pseudo_legal("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0", 3), 97862);
+        assert_eq!(perft_pseudo_legal("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0", 4), 43238);
+        assert_eq!(perft_pseudo_legal("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0", 3), 9467);
+        assert_eq!(perft_pseudo_legal("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 3), 62379);
+    }
 
-    println!("Time: {} ms", millis_since(&start));
 }
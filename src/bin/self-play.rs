@@ -0,0 +1,140 @@
+use std::env;
+use std::time::Instant;
+extern crate rustypawn;
+
+use rustypawn::Color;
+use rustypawn::Game;
+use rustypawn::GameStatus;
+use rustypawn::MAX_DEPTH;
+use rustypawn::SearchControl;
+use rustypawn::SearchInfo;
+use rustypawn::SearchOptions;
+use rustypawn::START_FEN;
+use rustypawn::ThinkInfo;
+use rustypawn::TimeLimits;
+use rustypawn::TranspositionTable;
+use rustypawn::make_move_algebraic;
+use rustypawn::millis_since;
+use rustypawn::think;
+
+// Plays the default search options against a variant given on the command
+// line, e.g. to measure a pruning change:
+//
+//     self-play 200 100 lmr=false
+//
+// plays 200 games at 100 ms per move. Each opening is played twice with
+// colors reversed.
+
+const OPENINGS: [&str; 8] = [
+    "e2e4 e7e5 g1f3 b8c6",
+    "d2d4 d7d5 c2c4 e7e6",
+    "e2e4 c7c5 g1f3 d7d6",
+    "d2d4 g8f6 c2c4 g7g6",
+    "e2e4 e7e6 d2d4 d7d5",
+    "c2c4 e7e5 b1c3 g8f6",
+    "e2e4 c7c6 d2d4 d7d5",
+    "g1f3 d7d5 g2g3 g8f6",
+];
+
+// Games still going after this many plies are scored as draws.
+const MAX_PLIES: usize = 400;
+
+struct Silent {}
+
+impl ThinkInfo for Silent {
+    fn think_info(self: &mut Silent, _info: &SearchInfo) {}
+}
+
+fn parse_setting(options: &mut SearchOptions, setting: &str) -> Result<(), String> {
+    let (name, value) = match setting.split_once('=') {
+        Some(pair) => pair,
+        None => return Err(format!("Expected name=value, got '{}'", setting)),
+    };
+    let invalid = || format!("Illegal value '{}' for '{}'", value, name);
+    match name {
+        "null-verification" => options.null_move_verification = value.parse().map_err(|_| invalid())?,
+        "lmr" => options.late_move_reductions = value.parse().map_err(|_| invalid())?,
+        "lmr-base" => options.lmr_base = value.parse().map_err(|_| invalid())?,
+        "lmr-divisor" => options.lmr_divisor = value.parse().map_err(|_| invalid())?,
+        "lmp" => options.late_move_pruning = value.parse().map_err(|_| invalid())?,
+        "lmp-base" => options.lmp_base = value.parse().map_err(|_| invalid())?,
+        "qchecks" => options.quiescence_checks = value.parse().map_err(|_| invalid())?,
+        "delta" => options.delta_pruning = value.parse().map_err(|_| invalid())?,
+        "delta-margin" => options.delta_margin = value.parse().map_err(|_| invalid())?,
+        _ => return Err(format!("Unknown setting '{}'", name)),
+    }
+    Ok(())
+}
+
+// Plays one game and returns the score of the first engine: 1 for a win,
+// 0.5 for a draw and 0 for a loss.
+fn play_game(opening: &str, first_is_white: bool, first: &SearchOptions, second: &SearchOptions,
+             movetime: u64) -> f64 {
+    let mut game = Game::from_fen(START_FEN).unwrap();
+    for mv in opening.split_whitespace() {
+        make_move_algebraic(&mut game, mv).unwrap();
+    }
+    let tts = [TranspositionTable::new(16), TranspositionTable::new(16)];
+    let mut plies = 0;
+    while game.status() == GameStatus::Ongoing && plies < MAX_PLIES {
+        let engine = if game.white_to_move() == first_is_white { 0 } else { 1 };
+        let options = if engine == 0 { first } else { second };
+        let result = think(&mut game, &tts[engine], &TimeLimits::movetime(movetime), MAX_DEPTH, options,
+                           &SearchControl::new(false), &mut Silent {});
+        match result {
+            Some(result) => game.make_move(result.best_move),
+            None => break,
+        };
+        plies += 1;
+    }
+    match game.status() {
+        GameStatus::Checkmate(winner) if (winner == Color::White) == first_is_white => 1.0,
+        GameStatus::Checkmate(_) => 0.0,
+        _ => 0.5,
+    }
+}
+
+// The rating difference that gives the expected score.
+fn elo(score: f64) -> f64 {
+    if score > 0.0 && score < 1.0 { -400.0 * (1.0 / score - 1.0).log10() } else { f64::NAN }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let games: usize = args.first().and_then(|s| s.parse().ok()).unwrap_or(16);
+    let movetime: u64 = args.get(1).and_then(|s| s.parse().ok()).unwrap_or(100);
+    let baseline = SearchOptions::default();
+    let mut variant = SearchOptions::default();
+    for setting in args.iter().skip(2) {
+        if let Err(e) = parse_setting(&mut variant, setting) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let start = Instant::now();
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+    for n in 0..games {
+        let opening = OPENINGS[(n / 2) % OPENINGS.len()];
+        let score = play_game(opening, n % 2 == 0, &variant, &baseline, movetime);
+        if score == 1.0 {
+            wins += 1;
+        } else if score == 0.0 {
+            losses += 1;
+        } else {
+            draws += 1;
+        }
+        println!("Game {}: {} (variant +{} ={} -{})", n + 1, score, wins, draws, losses);
+    }
+
+    let n = games.max(1) as f64;
+    let score = (wins as f64 + draws as f64 / 2.0) / n;
+    // the 95% confidence interval of the score, from the spread of the game results
+    let variance = (wins as f64 * (1.0 - score).powi(2) + draws as f64 * (0.5 - score).powi(2)
+                    + losses as f64 * score.powi(2)) / n;
+    let margin = 1.96 * (variance / n).sqrt();
+    let (low, high) = (elo(score - margin), elo(score + margin));
+    println!("Variant: +{} ={} -{}, score {:.1}%, elo {:+.0} +/- {:.0} ({:+.0} to {:+.0})",
+             wins, draws, losses, 100.0 * score, elo(score), (high - low) / 2.0, low, high);
+    println!("Time: {} ms", millis_since(&start));
+}
@@ -1,29 +1,42 @@
 use std::time::Instant;
 extern crate rustypawn;
 
+use rustypawn::Bound;
 use rustypawn::Game;
-use rustypawn::MoveTrait;
+use rustypawn::SearchControl;
+use rustypawn::SearchInfo;
+use rustypawn::SearchOptions;
 use rustypawn::ThinkInfo;
+use rustypawn::TimeLimits;
+use rustypawn::TranspositionTable;
 use rustypawn::think;
 use rustypawn::millis_since;
 
 struct Comms {}
 
 impl ThinkInfo for Comms {
-    fn think_info(self: &mut Comms, depth: usize, score: isize, mate_in: isize, node_count: usize, millis: u64, moves: &Vec<String>) {
-        let nps = if millis > 0 { 1000 * node_count as u64 / millis } else { 0 };
-        let mate = if mate_in != 0 { format!(" mate {}", mate_in) } else { String::new() };
-        println!("info depth {} score cp {}{} nodes {} time {} nps {} pv {}",
-            depth, score, mate, node_count, millis, nps, moves.join(" "));
+    fn think_info(self: &mut Comms, info: &SearchInfo) {
+        let nps = (1000 * info.node_count as u64).checked_div(info.millis).unwrap_or(0);
+        let mate = if info.mate_in != 0 { format!(" mate {}", info.mate_in) } else { String::new() };
+        let bound = match info.bound {
+            Bound::Exact => "",
+            Bound::Lower => " lowerbound",
+            Bound::Upper => " upperbound",
+        };
+        let moves: Vec<String> = info.pv.iter().map(|m| m.to_algebraic()).collect();
+        println!("info depth {} score cp {}{}{} nodes {} time {} nps {} pv {}",
+            info.depth, info.score, mate, bound, info.node_count, info.millis, nps, moves.join(" "));
     }
 }
 
 fn think_test(fen: &str, depth: usize) {
     let mut game = Game::from_fen(fen).unwrap();
     let mut comms = Comms {};
+    let tt = TranspositionTable::new(16);
 
-    let mv = match think(&mut game, 1 << 20, depth, &mut comms) {
-        Some(m) => m,
+    let mv = match think(&mut game, &tt, &TimeLimits::INFINITE, depth, &SearchOptions::default(),
+                         &SearchControl::new(false), &mut comms) {
+        Some(result) => result.best_move,
         None => panic!("No legal move")
     };
 
@@ -0,0 +1,219 @@
+use std::sync::OnceLock;
+
+// Bitboards use the engine's 8x8 square numbering: a8 = 0, b8 = 1, ..., h1 = 63.
+
+pub const FILE_A: u64 = 0x0101_0101_0101_0101;
+pub const FILE_H: u64 = FILE_A << 7;
+pub const DARK_SQUARES: u64 = 0x55aa_55aa_55aa_55aa;
+
+const KNIGHT_STEPS: [(isize, isize); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+const KING_STEPS: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+const BISHOP_DIRECTIONS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const ROOK_DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+// (file, row) steps from each square, row 0 being the eighth rank
+const fn step_table(steps: &[(isize, isize); 8]) -> [u64; 64] {
+    let mut table = [0u64; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let mut k = 0;
+        while k < 8 {
+            let f = (sq % 8) as isize + steps[k].0;
+            let r = (sq / 8) as isize + steps[k].1;
+            if f >= 0 && f < 8 && r >= 0 && r < 8 {
+                table[sq] |= 1 << (r * 8 + f);
+            }
+            k += 1;
+        }
+        sq += 1;
+    }
+    table
+}
+
+const fn pawn_table() -> [[u64; 64]; 2] {
+    let mut table = [[0u64; 64]; 2];
+    let mut sq = 0;
+    while sq < 64 {
+        let bit = 1u64 << sq;
+        // white pawns capture towards the eighth rank (lower indices)
+        table[0][sq] = ((bit & !FILE_A) >> 9) | ((bit & !FILE_H) >> 7);
+        table[1][sq] = ((bit & !FILE_A) << 7) | ((bit & !FILE_H) << 9);
+        sq += 1;
+    }
+    table
+}
+
+pub static KNIGHT_ATTACKS: [u64; 64] = step_table(&KNIGHT_STEPS);
+pub static KING_ATTACKS: [u64; 64] = step_table(&KING_STEPS);
+// indexed by color (0 white, 1 black) and square
+pub static PAWN_ATTACKS: [[u64; 64]; 2] = pawn_table();
+
+pub struct Bits(pub u64);
+
+impl Iterator for Bits {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            None
+        } else {
+            let sq = self.0.trailing_zeros() as usize;
+            self.0 &= self.0 - 1;
+            Some(sq)
+        }
+    }
+}
+
+pub fn lowest_square(bb: u64) -> usize {
+    bb.trailing_zeros() as usize
+}
+
+fn ray_attacks(sq: usize, occupied: u64, directions: &[(isize, isize); 4]) -> u64 {
+    let mut attacks = 0;
+    for &(df, dr) in directions.iter() {
+        let mut f = (sq % 8) as isize + df;
+        let mut r = (sq / 8) as isize + dr;
+        while (0..8).contains(&f) && (0..8).contains(&r) {
+            let bit = 1u64 << (r * 8 + f);
+            attacks |= bit;
+            if occupied & bit != 0 {
+                break;
+            }
+            f += df;
+            r += dr;
+        }
+    }
+    attacks
+}
+
+// The squares whose occupancy matters: the rays without their final square.
+fn relevant_mask(sq: usize, directions: &[(isize, isize); 4]) -> u64 {
+    let mut mask = 0;
+    for &(df, dr) in directions.iter() {
+        let mut f = (sq % 8) as isize + df;
+        let mut r = (sq / 8) as isize + dr;
+        while (0..8).contains(&(f + df)) && (0..8).contains(&(r + dr)) {
+            mask |= 1u64 << (r * 8 + f);
+            f += df;
+            r += dr;
+        }
+    }
+    mask
+}
+
+#[derive(Clone, Copy)]
+struct Magic {
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+struct SliderTables {
+    bishops: [Magic; 64],
+    rooks: [Magic; 64],
+    attacks: Vec<u64>,
+}
+
+static SLIDER_TABLES: OnceLock<SliderTables> = OnceLock::new();
+
+fn init_magics(magics: &[u64; 64], directions: &[(isize, isize); 4], attacks: &mut Vec<u64>) -> [Magic; 64] {
+    let mut result = [Magic { mask: 0, magic: 0, shift: 0, offset: 0 }; 64];
+    for (sq, entry) in result.iter_mut().enumerate() {
+        let mask = relevant_mask(sq, directions);
+        let bits = mask.count_ones();
+        *entry = Magic { mask, magic: magics[sq], shift: 64 - bits, offset: attacks.len() };
+        attacks.resize(attacks.len() + (1 << bits), 0);
+        // enumerate all subsets of the mask (Carry-Rippler)
+        let mut occupied: u64 = 0;
+        loop {
+            let index = entry.offset + (occupied.wrapping_mul(entry.magic) >> entry.shift) as usize;
+            let slot = ray_attacks(sq, occupied, directions);
+            debug_assert!(attacks[index] == 0 || attacks[index] == slot, "bad magic for square {}", sq);
+            attacks[index] = slot;
+            occupied = occupied.wrapping_sub(mask) & mask;
+            if occupied == 0 {
+                break;
+            }
+        }
+    }
+    result
+}
+
+fn slider_tables() -> &'static SliderTables {
+    SLIDER_TABLES.get_or_init(|| {
+        let mut attacks = Vec::with_capacity(108 * 1024);
+        let bishops = init_magics(&BISHOP_MAGICS, &BISHOP_DIRECTIONS, &mut attacks);
+        let rooks = init_magics(&ROOK_MAGICS, &ROOK_DIRECTIONS, &mut attacks);
+        SliderTables { bishops, rooks, attacks }
+    })
+}
+
+pub fn bishop_attacks(sq: usize, occupied: u64) -> u64 {
+    let tables = slider_tables();
+    let m = &tables.bishops[sq];
+    tables.attacks[m.offset + ((occupied & m.mask).wrapping_mul(m.magic) >> m.shift) as usize]
+}
+
+pub fn rook_attacks(sq: usize, occupied: u64) -> u64 {
+    let tables = slider_tables();
+    let m = &tables.rooks[sq];
+    tables.attacks[m.offset + ((occupied & m.mask).wrapping_mul(m.magic) >> m.shift) as usize]
+}
+
+pub fn queen_attacks(sq: usize, occupied: u64) -> u64 {
+    bishop_attacks(sq, occupied) | rook_attacks(sq, occupied)
+}
+
+// The squares strictly between two squares on a common line, or nothing.
+pub fn between(a: usize, b: usize) -> u64 {
+    let a_bit = 1u64 << a;
+    let b_bit = 1u64 << b;
+    if rook_attacks(a, 0) & b_bit != 0 {
+        rook_attacks(a, b_bit) & rook_attacks(b, a_bit)
+    } else if bishop_attacks(a, 0) & b_bit != 0 {
+        bishop_attacks(a, b_bit) & bishop_attacks(b, a_bit)
+    } else {
+        0
+    }
+}
+
+// Found with a sparse random search (xorshift64, seed 0x2545f4914f6cdd1d) for
+// the minimal number of index bits per square.
+const BISHOP_MAGICS: [u64; 64] = [
+    0x1002200101020088, 0x4004010404049080, 0x200404008a020422, 0x000806004c002202,
+    0x8001104110000090, 0x8002080248000202, 0x0000440484408000, 0x0002010082012020,
+    0x0488406912288201, 0x0101600202004110, 0x0810100400842440, 0x0800240410822002,
+    0x0000020210010030, 0x8100008260210008, 0x4404004404044201, 0x2440008084104200,
+    0x88220a08207c0280, 0x4104801001080120, 0x4110020808304010, 0x2518050402400980,
+    0x0002000422010010, 0x0403020080a00100, 0x0244200200822880, 0x0608200200820860,
+    0x81202818c6100400, 0x4081501088100100, 0x2002491448020400, 0x0070040000440008,
+    0x0880820004010400, 0x8490108202c80400, 0x0004004204050c41, 0x800102400c241402,
+    0x1014200a00041013, 0x0008110400300400, 0x0042060900220800, 0x00d2200800010105,
+    0x0c0a0484008a0020, 0x801001010802100c, 0x0919920202440101, 0x06020a0202604050,
+    0x4202083240260821, 0x0118520260221008, 0x00000c4402021001, 0x1010006018020100,
+    0x9600204c10130500, 0x00c0810403000020, 0x0002640420801401, 0x8001040400900840,
+    0x1002221004048000, 0x0020808808420401, 0x0100010080908040, 0x2000800884040820,
+    0x1000002020444b28, 0x01180890d0008888, 0x4090448800940100, 0x0160380a00404240,
+    0x5002042109101080, 0x800800220d300800, 0x0808200840441004, 0x8008022006150402,
+    0x2000000070020213, 0x0202414119211100, 0x8820620204180484, 0x8012100115010208,
+];
+
+const ROOK_MAGICS: [u64; 64] = [
+    0x0880102080004000, 0x8340004410002000, 0x0880200090008268, 0x0080080080100004,
+    0x8100110004020800, 0x0300010004000822, 0x08801a0029000080, 0x8100050001204882,
+    0x0844800081400320, 0x0804402010004000, 0x0108802003100480, 0x0004808008001000,
+    0x0003001801001014, 0x0002000200041008, 0x0004008108042210, 0x0105000100009042,
+    0x0400808000400021, 0xc100404010002000, 0x0060008010002088, 0x0400808008001000,
+    0x4440808008000400, 0x1002008004000280, 0x40024400300d1248, 0x0010020000408104,
+    0x0101008200204200, 0x8020002040005000, 0x4100100080802000, 0x4008006a80100280,
+    0x1020080080040080, 0x0004010040020040, 0x0018a12400080290, 0x6140004200008104,
+    0x4000400020800090, 0x2020002080804000, 0x0000408202002010, 0x0080100501000820,
+    0x0000800400800800, 0x000a200408014010, 0x0100800200800100, 0xa00800570200008c,
+    0x008000406000c010, 0x1040100028002000, 0x0048200100110040, 0x0068490210030020,
+    0x1009080005010010, 0x2142000804010100, 0x1001080110840002, 0x1801004400820001,
+    0x010440208d020200, 0x0000400020008080, 0x0200200080100280, 0x0000100020090100,
+    0x0204008008020480, 0x8104010040020040, 0x78000201b0080400, 0x0040800051002880,
+    0x0050108001002041, 0x208a801100614003, 0x0006002042089082, 0x0011090004201001,
+    0x1002001004200802, 0x0005000208040001, 0x0002002701ac0822, 0x000010250184004a,
+];
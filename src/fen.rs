@@ -0,0 +1,326 @@
+use std::fmt;
+
+use crate::*;
+
+// Positions are byte offsets into the FEN string of the offending character
+// or field.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FenError {
+    Empty,
+    InvalidPiece(usize),     // unknown character or a pawn on the first or last rank
+    RankLength(usize),       // a rank with more or less than eight squares
+    RankCount(usize),        // not eight ranks
+    Kings(usize),            // not exactly one king per side
+    OpponentInCheck(usize),  // the king of the side not to move can be taken
+    SideToMove(usize),
+    Castling(usize),
+    EnPassant(usize),
+    HalfmoveClock(usize),
+    FullmoveNumber(usize),
+    TrailingInput(usize),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (msg, pos) = match *self {
+            FenError::Empty => return f.write_str("empty FEN"),
+            FenError::InvalidPiece(pos) => ("illegal piece", pos),
+            FenError::RankLength(pos) => ("rank does not have eight squares", pos),
+            FenError::RankCount(pos) => ("board does not have eight ranks", pos),
+            FenError::Kings(pos) => ("each side must have one king", pos),
+            FenError::OpponentInCheck(pos) => ("side not to move is in check", pos),
+            FenError::SideToMove(pos) => ("illegal side to move", pos),
+            FenError::Castling(pos) => ("illegal castling rights", pos),
+            FenError::EnPassant(pos) => ("illegal en passant square", pos),
+            FenError::HalfmoveClock(pos) => ("illegal halfmove clock", pos),
+            FenError::FullmoveNumber(pos) => ("illegal fullmove number", pos),
+            FenError::TrailingInput(pos) => ("unexpected input", pos),
+        };
+        write!(f, "{} at position {}", msg, pos)
+    }
+}
+
+// The whitespace separated fields of s with their byte offsets.
+fn fields(s: &str) -> Vec<(usize, &str)> {
+    let mut result = Vec::new();
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(st)) => {
+                result.push((st, &s[st..i]));
+                start = None;
+            },
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(st) = start {
+        result.push((st, &s[st..]));
+    }
+    result
+}
+
+// Each castling right with the home squares of its king and rook.
+const CASTLING_CHARS: [(char, usize, usize, usize); 4] = [
+    ('K', CASTLING_KING_WHITE, 60, 63), ('Q', CASTLING_QUEEN_WHITE, 60, 56),
+    ('k', CASTLING_KING_BLACK, 4, 7), ('q', CASTLING_QUEEN_BLACK, 4, 0),
+];
+
+impl Game {
+
+    // Reads a position in Forsyth-Edwards Notation. The castling, en passant,
+    // halfmove clock and fullmove number fields may be left out.
+    pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+        let fields = fields(fen);
+        let mut game = Game::new();
+
+        let (board_pos, board) = *fields.first().ok_or(FenError::Empty)?;
+        if board.split('/').count() != 8 {
+            return Err(FenError::RankCount(board_pos));
+        }
+        let mut rank_start = board_pos;
+        for (row, rank) in board.split('/').enumerate() {
+            let mut file = 0;
+            for (i, c) in rank.char_indices() {
+                if let Some(n) = c.to_digit(10) {
+                    if !(1..=8).contains(&n) {
+                        return Err(FenError::InvalidPiece(rank_start + i));
+                    }
+                    file += n as usize;
+                    continue;
+                }
+                let piece = match PIECE_ASCII.find(c) {
+                    Some(idx) if idx > 0 => PIECE_VALUES[idx],
+                    _ => return Err(FenError::InvalidPiece(rank_start + i))
+                };
+                if file >= 8 {
+                    return Err(FenError::RankLength(rank_start));
+                }
+                if piece & PIECE_MASK == PAWN && (row == 0 || row == 7) {
+                    return Err(FenError::InvalidPiece(rank_start + i));
+                }
+                game.put_piece(8 * row + file, piece);
+                file += 1;
+            }
+            if file != 8 {
+                return Err(FenError::RankLength(rank_start));
+            }
+            rank_start += rank.len() + 1;
+        }
+        if game.pieces_of(WHITE_KING).count_ones() != 1 || game.pieces_of(BLACK_KING).count_ones() != 1 {
+            return Err(FenError::Kings(board_pos));
+        }
+
+        let side = match fields.get(1) {
+            Some(&(_, "w")) | Some(&(_, "W")) => WHITE,
+            Some(&(_, "b")) | Some(&(_, "B")) => BLACK,
+            Some(&(p, _)) => return Err(FenError::SideToMove(p)),
+            None => return Err(FenError::SideToMove(fen.len()))
+        };
+        let xside = if side == WHITE { BLACK } else { WHITE };
+        if game.is_attacked_by(game.king_position(xside), side) {
+            return Err(FenError::OpponentInCheck(board_pos));
+        }
+
+        let mut castling: usize = 0;
+        if let Some(&(p, s)) = fields.get(2) {
+            if s != "-" {
+                for (i, c) in s.char_indices() {
+                    let color = if c.is_ascii_uppercase() { WHITE } else { BLACK };
+                    match CASTLING_CHARS.iter().find(|&&(ch, _, _, _)| ch == c) {
+                        Some(&(_, bit, king, rook)) if castling & bit == 0 && game.board[king] == KING | color
+                                && game.board[rook] == ROOK | color => castling |= bit,
+                        _ => return Err(FenError::Castling(p + i))
+                    }
+                }
+            }
+        }
+
+        let mut ep = 0;
+        if let Some(&(p, s)) = fields.get(3) {
+            if s != "-" {
+                // the square passed by a pawn of the side not to move
+                let (rank, pawn) = if side == WHITE { (5, BLACK_PAWN) } else { (2, WHITE_PAWN) };
+                ep = match Square::from_algebraic(s) {
+                    Some(sq) if sq.rank() == rank => sq.index(),
+                    _ => return Err(FenError::EnPassant(p))
+                };
+                let pushed = if side == WHITE { ep + 8 } else { ep - 8 };
+                if game.board[pushed] != pawn || game.occupied() & (1 << ep) != 0 {
+                    return Err(FenError::EnPassant(p));
+                }
+            }
+        }
+
+        let draw_ply = match fields.get(4) {
+            // kept in the state below the captured piece of a history item,
+            // which leaves it 32 bits
+            Some(&(p, s)) => match s.parse::<u32>() {
+                Ok(v) => v as usize,
+                Err(_) => return Err(FenError::HalfmoveClock(p))
+            },
+            None => 0
+        };
+
+        let fullmove = match fields.get(5) {
+            Some(&(p, s)) => match s.parse::<usize>() {
+                Ok(v) if v > 0 => v,
+                _ => return Err(FenError::FullmoveNumber(p))
+            },
+            None => 1
+        };
+
+        if let Some(&(p, _)) = fields.get(6) {
+            return Err(FenError::TrailingInput(p));
+        }
+
+        game.state = draw_ply << 24 | ep << 16 | castling << 8 | side;
+        game.start_ply = 2 * (fullmove - 1) + if side == BLACK { 1 } else { 0 };
+        game.hash = game.compute_hash();
+        Ok(game)
+    }
+
+    pub fn fullmove_number(self: &Game) -> usize {
+        (self.start_ply + self.history.len()) / 2 + 1
+    }
+
+    pub fn to_fen(self: &Game) -> String {
+        let mut fen = String::new();
+        for rank in 0..8 {
+            let mut empty = 0;
+            for file in 0..8 {
+                let piece = self.board[8 * rank + file];
+                if piece == EMPTY {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                let idx = PIECE_VALUES.iter().position(|&p| p == piece).unwrap();
+                fen.push(PIECE_ASCII.as_bytes()[idx] as char);
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank < 7 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(if self.white_to_move() { " w " } else { " b " });
+
+        let castling = (self.state >> 8) & 15;
+        if castling == 0 {
+            fen.push('-');
+        }
+        for &(c, bit, _, _) in CASTLING_CHARS.iter() {
+            if castling & bit != 0 {
+                fen.push(c);
+            }
+        }
+
+        let ep = self.state.ep();
+        if ep != 0 {
+            fen.push(' ');
+            fen.push_str(&Square(ep as u8).to_string());
+        } else {
+            fen.push_str(" -");
+        }
+
+        fen.push_str(&format!(" {} {}", self.state.draw_ply(), self.fullmove_number()));
+        fen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERFT_FENS: [&str; 6] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    ];
+
+    #[test]
+    fn fen_round_trip() {
+        for fen in PERFT_FENS.iter() {
+            let mut game = Game::from_fen(fen).unwrap();
+            assert_eq!(game.to_fen(), *fen);
+            for mv in game.legal_moves() {
+                game.make_move(mv);
+                let copy = Game::from_fen(&game.to_fen()).unwrap();
+                assert_eq!(copy.to_fen(), game.to_fen());
+                assert_eq!(copy.hash(), game.hash(), "{} after {}", fen, mv);
+                game.unmake_move(mv);
+            }
+        }
+        let game = Game::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -").unwrap();
+        assert_eq!(game.to_fen(), "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1");
+    }
+
+    #[test]
+    fn tracks_move_numbers() {
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        for mv in ["e2e4", "c7c5", "g1f3"].iter() {
+            game.make_move(algebraic_to_move(mv).unwrap());
+        }
+        assert_eq!(game.to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 7 40").unwrap();
+        game.make_move(algebraic_to_move("e8d8").unwrap());
+        assert_eq!(game.fullmove_number(), 41);
+        game.make_move(algebraic_to_move("e2e4").unwrap());
+        assert_eq!(game.to_fen(), "3k4/8/8/8/4P3/8/8/4K3 b - e3 0 41");
+        // a clock past the fifty-move rule is kept as it is
+        let game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 100 90").unwrap();
+        assert!(game.fifty_move_draw());
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 150 90").unwrap();
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 150 90");
+        let mv = algebraic_to_move("e1d1").unwrap();
+        game.make_move(mv);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/4P3/3K4 b - - 151 90");
+        game.unmake_move(mv);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 150 90");
+        let game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 4294967295 90").unwrap();
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 4294967295 90");
+    }
+
+    #[test]
+    fn reports_fen_errors() {
+        let error = |fen: &str| Game::from_fen(fen).err();
+        assert_eq!(error("  "), Some(FenError::Empty));
+        assert_eq!(error("rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w"), Some(FenError::InvalidPiece(13)));
+        assert_eq!(error("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w"), Some(FenError::InvalidPiece(18)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNP w"), Some(FenError::InvalidPiece(42)));
+        assert_eq!(error("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w"), Some(FenError::RankLength(9)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w"), Some(FenError::RankLength(35)));
+        assert_eq!(error("rnbqkbnr/pppppppp/72/8/8/8/PPPPPPPP/RNBQKBNR w"), Some(FenError::RankLength(18)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w"), Some(FenError::RankCount(0)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/8/8/PPPPPPPP/RNBQKBNR w"), Some(FenError::RankCount(0)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQQBNR w"), Some(FenError::Kings(0)));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4RK2 w - - 0 1"), Some(FenError::OpponentInCheck(0)));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4RK2 b - - 0 1").is_ok());
+        assert_eq!(error(" rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x"), Some(FenError::SideToMove(45)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR"), Some(FenError::SideToMove(43)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx"), Some(FenError::Castling(49)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQKq"), Some(FenError::Castling(48)));
+        // a right without its king or rook at home
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w K - 0 1"), Some(FenError::Castling(22)));
+        assert_eq!(error("r3k2r/8/8/8/8/8/8/R2K3R w KQkq - 0 1"), Some(FenError::Castling(26)));
+        assert_eq!(error("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"), None);
+        assert_eq!(error("1r2k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"), Some(FenError::Castling(30)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3"), Some(FenError::EnPassant(51)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e6"), Some(FenError::EnPassant(51)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x"), Some(FenError::HalfmoveClock(53)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - -1"), Some(FenError::HalfmoveClock(53)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 4294967296"), Some(FenError::HalfmoveClock(53)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0"), Some(FenError::FullmoveNumber(55)));
+        assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 x"), Some(FenError::TrailingInput(57)));
+        assert_eq!(FenError::Castling(49).to_string(), "illegal castling rights at position 49");
+    }
+}
@@ -1,6 +1,26 @@
-use rand::prelude::*;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::time::Instant;
 
+mod bitboard;
+mod fen;
+mod movepick;
+mod options;
+mod pgn;
+mod san;
+mod time_manager;
+mod tt;
+
+use bitboard::*;
+
+pub use fen::FenError;
+pub use movepick::MovePicker;
+pub use options::{EngineOption, OptionError, OptionRegistry, OptionType};
+pub use pgn::{read_pgn, write_pgn, PgnError, PgnErrorKind, PgnEval, PgnGame, PgnMove, PgnReader};
+pub use san::SanError;
+pub use time_manager::{TimeControl, TimeLimits};
+pub use tt::{Bound, TranspositionTable};
+
 const EMPTY: usize = 0;
 const PAWN: usize = 1;
 const BISHOP: usize = 2;
@@ -10,7 +30,6 @@ const QUEEN: usize = 5;
 const KING: usize = 6;
 const WHITE: usize = 64;
 const BLACK: usize = 128;
-const OFF_BOARD: usize = WHITE | BLACK;
 const COLOR_MASK: usize = WHITE | BLACK;
 const PIECE_MASK: usize = 7;
 const CASTLING_KING_WHITE: usize = 1;
@@ -30,56 +49,140 @@ const BLACK_ROOK: usize = BLACK | ROOK;
 const BLACK_QUEEN: usize = BLACK | QUEEN;
 const BLACK_KING: usize = BLACK | KING;
 
-const CASTLE_MASK: [usize; 120] = [
-    0,  0,  0,  0,  0,  0,  0,  0,  0, 0,
-    0,  0,  0,  0,  0,  0,  0,  0,  0, 0,
-    0,  7, 15, 15, 15,  3, 15, 15, 11, 0,
-    0, 15, 15, 15, 15, 15, 15, 15, 15, 0,
-    0, 15, 15, 15, 15, 15, 15, 15, 15, 0,
-    0, 15, 15, 15, 15, 15, 15, 15, 15, 0,
-    0, 15, 15, 15, 15, 15, 15, 15, 15, 0,
-    0, 15, 15, 15, 15, 15, 15, 15, 15, 0,
-    0, 15, 15, 15, 15, 15, 15, 15, 15, 0,
-    0, 13, 15, 15, 15, 12, 15, 15, 14, 0,
-    0,  0,  0,  0,  0,  0,  0,  0,  0, 0,
-    0,  0,  0,  0,  0,  0,  0,  0,  0, 0,
+const CASTLE_MASK: [usize; 64] = [
+     7, 15, 15, 15,  3, 15, 15, 11,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    13, 15, 15, 15, 12, 15, 15, 14,
 ];
 
-fn pos_to_algebraic(pos: usize) -> String {
-    format!("{}{}", (96 + (pos % 10)) as u8 as char, (58 - (pos / 10)) as u8 as char)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    fn from_code(color: usize) -> Color {
+        if color == WHITE { Color::White } else { Color::Black }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Piece {
+    Pawn = PAWN as isize,
+    Bishop = BISHOP as isize,
+    Knight = KNIGHT as isize,
+    Rook = ROOK as isize,
+    Queen = QUEEN as isize,
+    King = KING as isize,
+}
+
+impl Piece {
+    fn from_code(piece: usize) -> Piece {
+        match piece & PIECE_MASK {
+            PAWN => Piece::Pawn,
+            BISHOP => Piece::Bishop,
+            KNIGHT => Piece::Knight,
+            ROOK => Piece::Rook,
+            QUEEN => Piece::Queen,
+            _ => Piece::King,
+        }
+    }
 }
 
-pub type Move = u64;   // promoted << 16 | to << 8 | from
+// a8 = 0, b8 = 1, ..., h1 = 63
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Square(u8);
+
+impl Square {
+    // file 0..8 is a..h, rank 0..8 is 1..8
+    pub fn new(file: usize, rank: usize) -> Square {
+        assert!(file < 8 && rank < 8, "Square::new: off the board");
+        Square((8 * (7 - rank) + file) as u8)
+    }
+
+    pub fn from_algebraic(s: &str) -> Option<Square> {
+        let mut iter = s.chars();
+        let file = match iter.next() {
+            Some(c @ 'a'..='h') => c as usize - 'a' as usize,
+            _ => return None
+        };
+        let rank = match iter.next() {
+            Some(c @ '1'..='8') => c as usize - '1' as usize,
+            _ => return None
+        };
+        match iter.next() {
+            Some(_) => None,
+            None => Some(Square::new(file, rank))
+        }
+    }
+
+    pub fn file(self) -> usize {
+        self.0 as usize % 8
+    }
+
+    pub fn rank(self) -> usize {
+        7 - self.0 as usize / 8
+    }
 
-const DUMMY_MOVE: Move = 0;  // non-existent move
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
 
-pub trait MoveTrait {
-    fn new_basic(from: usize, to: usize) -> Move;
-    fn new_promotion(from: usize, to: usize, promotion: usize) -> Move;
-    fn from(self) -> usize;
-    fn to(self) -> usize;
-    fn promotion(self) -> usize;
-    fn to_algebraic(self) -> String;
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.file() as u8) as char, self.rank() + 1)
+    }
 }
 
-impl MoveTrait for Move {
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Move(u32);  // promoted << 16 | to << 8 | from
+
+const DUMMY_MOVE: Move = Move(0);  // non-existent move
+
+impl Move {
+    // None for a promotion to a pawn or a king.
+    pub fn new(from: Square, to: Square, promotion: Option<Piece>) -> Option<Move> {
+        match promotion {
+            Some(Piece::Pawn) | Some(Piece::King) => None,
+            _ => Some(Move::new_promotion(from.index(), to.index(), promotion.map_or(EMPTY, |p| p as usize))),
+        }
+    }
     fn new_basic(from: usize, to: usize) -> Move {
-        (to << 8 | from) as Move
+        Move((to << 8 | from) as u32)
     }
     fn new_promotion(from: usize, to: usize, promotion: usize) -> Move {
-        (promotion << 16 | to << 8 | from) as Move
+        Move((promotion << 16 | to << 8 | from) as u32)
+    }
+    pub fn from(self) -> Square {
+        Square(self.0 as u8)
     }
-    fn from(self) -> usize {
-        (self & 0xff) as usize
+    pub fn to(self) -> Square {
+        Square((self.0 >> 8) as u8)
+    }
+    pub fn promotion(self) -> Option<Piece> {
+        match self.promoted() {
+            EMPTY => None,
+            p => Some(Piece::from_code(p))
+        }
     }
-    fn to(self) -> usize {
-        ((self >> 8) & 0xff) as usize
+    fn promoted(self) -> usize {
+        (self.0 >> 16) as usize
     }
-    fn promotion(self) -> usize {
-        ((self >> 16) & 0xff) as usize
+    pub fn to_algebraic(self) -> String {
+        self.to_string()
     }
-    fn to_algebraic(self) -> String {
-        let promotion = match self.promotion() {
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let promotion = match self.promoted() {
             EMPTY => "",
             BISHOP => "b",
             KNIGHT => "n",
@@ -87,10 +190,47 @@ impl MoveTrait for Move {
             QUEEN => "q",
             _ => panic!("to_algebraic")
         };
-        format!("{}{}{}", pos_to_algebraic(self.from()), pos_to_algebraic(self.to()), promotion)
+        write!(f, "{}{}{}", self.from(), self.to(), promotion)
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate(Color),  // the winner
+    Stalemate,
+    FiftyMove,
+    ThreefoldRepetition,
+    InsufficientMaterial,
+}
+
+// What the search has learned about good moves in a position.
+pub struct OrderingHints<'a> {
+    pub pv_move: Move,
+    pub hash_move: Move,
+    pub killers: [Move; 2],  // quiet moves that caused cutoffs at the same ply
+    pub countermove: Move,  // the last quiet cutoff in reply to the previous move
+    pub history: &'a [i32; 64 * 64],  // for the side to move, by from * 64 + to
+}
+
+// Move ordering scores, best first. Quiet moves are scored by history
+// around ORDER_QUIET, and bad captures below that.
+const ORDER_PV: usize = 1000000000;
+const ORDER_HASH: usize = 900000000;
+const ORDER_GOOD_CAPTURE: usize = 800000000;
+const ORDER_KILLER: usize = 700000000;
+const ORDER_COUNTERMOVE: usize = 600000000;
+const ORDER_QUIET: i32 = 100000;
+
+// A move together with its move ordering score
+#[derive(Clone, Copy, Debug)]
+pub struct ScoredMove {
+    pub mv: Move,
+    pub score: usize,
+}
+
+// draw_ply << 24 | ep << 16 | castling << 8 | side to move, the halfmove
+// clock on top taking whatever bits are left
 type State = usize;
 
 trait StateTrait {
@@ -100,56 +240,76 @@ trait StateTrait {
 
 impl StateTrait for State {
     fn draw_ply(self) -> usize {
-        (self >> 24) & 127
+        self >> 24
     }
     fn ep(self) -> usize {
         (self >> 16) & 127
     }
 }
 
+#[derive(Clone)]
 struct HistoryItem {
-    unmove: u64,  // captured << 32 | state
+    unmove: u64,  // state << 8 | captured
     hash: u64,
 }
 
+#[derive(Clone)]
 pub struct Game {
-    board: [usize; 120],
+    board: [usize; 64],
+    pieces: [u64; 7],  // by piece type, PAWN..KING
+    colors: [u64; 2],  // white, black
     state: State,  // draw_ply << 24 | ep << 16 | castling << 8 | turn
-    king_white: usize,
-    king_black: usize,
-    piece_hashes: [u64; 12 * 64],
-    black_hash: u64,
-    castling_hashes: [u64; 16],
-    ep_hashes: [u64; 8],
     hash: u64,
     history: Vec<HistoryItem>,
+    start_ply: usize,  // plies played before the starting position
+    eval_params: EvalParams,
 }
 
-const REV8X8: [usize; 120] = [
-    99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
-	99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
-	99,  0,  1,  2,  3,  4,  5,  6,  7, 99,
-	99,  8,  9, 10, 11, 12, 13, 14, 15, 99,
-	99, 16, 17, 18, 19, 20, 21, 22, 23, 99,
-	99, 24, 25, 26, 27, 28, 29, 30, 31, 99,
-	99, 32, 33, 34, 35, 36, 37, 38, 39, 99,
-	99, 40, 41, 42, 43, 44, 45, 46, 47, 99,
-	99, 48, 49, 50, 51, 52, 53, 54, 55, 99,
-	99, 56, 57, 58, 59, 60, 61, 62, 63, 99,
-	99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
-	99, 99, 99, 99, 99, 99, 99, 99, 99, 99
-];
+// pin masks for move generation that does not care about pins
+const NO_PINS: [u64; 64] = [!0; 64];
 
-const MAP8X8: [usize; 64] = [
-    21, 22, 23, 24, 25, 26, 27, 28,
-    31, 32, 33, 34, 35, 36, 37, 38,
-    41, 42, 43, 44, 45, 46, 47, 48,
-    51, 52, 53, 54, 55, 56, 57, 58,
-    61, 62, 63, 64, 65, 66, 67, 68,
-    71, 72, 73, 74, 75, 76, 77, 78,
-    81, 82, 83, 84, 85, 86, 87, 88,
-    91, 92, 93, 94, 95, 96, 97, 98
-];
+fn color_index(color: usize) -> usize {
+    color >> 7
+}
+
+// Zobrist keys in the Polyglot layout: 12 * 64 piece keys (black pawn, white
+// pawn, black knight, ..., white king, each indexed by square with a1 = 0),
+// one key per castling right, one per en passant file and one for white to
+// move. The keys come from a fixed-seed generator, so every Game (in every
+// process) hashes the same position to the same value, but they are not
+// Polyglot's Random64 table: hashes cannot be used to probe Polyglot books.
+const ZOBRIST_CASTLING: usize = 768;
+const ZOBRIST_EP: usize = 772;
+const ZOBRIST_WHITE: usize = 780;
+const POLYGLOT_KIND: [usize; 7] = [0, 0, 2, 1, 3, 4, 5];
+
+static ZOBRIST_KEYS: [u64; 781] = zobrist_keys();
+
+const fn zobrist_keys() -> [u64; 781] {
+    // splitmix64
+    let mut keys = [0u64; 781];
+    let mut seed: u64 = 0x5275_7374_7950_6177;
+    let mut i = 0;
+    while i < 781 {
+        seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+fn castling_hash(castling: usize) -> u64 {
+    let mut hash = 0;
+    for bit in 0..4 {
+        if castling & (1 << bit) != 0 {
+            hash ^= ZOBRIST_KEYS[ZOBRIST_CASTLING + bit];
+        }
+    }
+    hash
+}
 
 const PIECE_ASCII: &str = " PBNRQKpbnrqk";
 const PIECE_VALUES: [usize; 13] = [
@@ -157,17 +317,17 @@ const PIECE_VALUES: [usize; 13] = [
     WHITE | PAWN, WHITE | BISHOP, WHITE | KNIGHT, WHITE | ROOK, WHITE | QUEEN, WHITE | KING,
     BLACK | PAWN, BLACK | BISHOP, BLACK | KNIGHT, BLACK | ROOK, BLACK | QUEEN, BLACK | KING
 ];
-const BISHOP_MOVEMENTS: [isize; 4] = [-11, -9, 9, 11];
-const KNIGHT_MOVEMENTS: [isize; 8] = [-21, -19, -12, -8, 8, 12, 19, 21];
-const ROOK_MOVEMENTS: [isize; 4] = [-1, 1, -10, 10];
-const KING_MOVEMENTS: [isize; 8] = [-1, 1, -10, 10, -11, -9, 9, 11];
+// by piece type, EMPTY..KING; the king's value is never used in exchanges
+const EXCHANGE_VALUES: [isize; 7] = [0, PAWN_VALUE, BISHOP_VALUE, KNIGHT_VALUE, ROOK_VALUE, QUEEN_VALUE, 0];
 const PAWN_VALUE: isize = 100;
 const KNIGHT_VALUE: isize = 300;
 const BISHOP_VALUE: isize = 300;
 const ROOK_VALUE: isize = 500;
 const QUEEN_VALUE: isize = 900;
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 pub const MAX_DEPTH: usize = 32;
 const MATE_VALUE: isize = 100000;
+const HISTORY_MAX: i32 = 16384;
 
 const PAWN_PCSQ: [isize; 64] = [
 	  0,   0,   0,   0,   0,   0,   0,   0,
@@ -235,50 +395,20 @@ const FLIP: [usize; 64] = [
 	  0,   1,   2,   3,   4,   5,   6,   7
 ];
 
-pub fn algebraic_to_pos(s: &str) -> Option<usize> {
-    let mut iter = s.chars();
-    let c = match iter.next() {
-        Option::Some(c) => {
-            match c.to_digit(18) {
-                Option::Some(v) => {
-                    if v < 10 {
-                        return Option::None
-                    }
-                    (v - 10) as usize
-                },
-                Option::None => return Option::None
-            }
-        },
-        Option::None => return Option::None
-    };
-    let r = match iter.next() {
-        Option::Some(c) => {
-            match c.to_digit(10) {
-                Option::Some(v) => (8 - v) as usize,
-                Option::None => return Option::None
-            }
-        },
-        Option::None => return Option::None
+// Reads a move in coordinate notation, such as e2e4 or e7e8q. The move is
+// not checked against any position.
+pub fn algebraic_to_move(s: &str) -> Result<Move, SanError> {
+    let from = s.get(0..2).and_then(Square::from_algebraic).ok_or(SanError::Invalid)?;
+    let to = s.get(2..4).and_then(Square::from_algebraic).ok_or(SanError::Invalid)?;
+    let promoted = match &s[4..] {
+        "b" => BISHOP,
+        "n" => KNIGHT,
+        "r" => ROOK,
+        "q" => QUEEN,
+        "" => EMPTY,
+        _ => return Err(SanError::Invalid)
     };
-    match iter.next() {
-        Option::Some(_) => Option::None,
-        Option::None => Option::Some(8 * r + c)
-    }
-}
-
-pub fn algebraic_to_move(s: &str) -> Move {
-    Move::new_promotion(
-        MAP8X8[algebraic_to_pos(&s[0..2]).unwrap()],
-        MAP8X8[algebraic_to_pos(&s[2..4]).unwrap()],
-        match &s[4..] {
-            "b" => BISHOP,
-            "n" => KNIGHT,
-            "r" => ROOK,
-            "q" => QUEEN,
-            "" => EMPTY,
-            _ => panic!("algebraic_to_move: illegal promotion")
-        }
-    )
+    Ok(Move::new_promotion(from.index(), to.index(), promoted))
 }
 
 fn add_move(move_list: &mut Vec<Move>, from: usize, to: usize) {
@@ -292,52 +422,77 @@ fn add_promotion(move_list: &mut Vec<Move>, from: usize, to: usize) {
     move_list.push(Move::new_promotion(from, to, QUEEN));
 }
 
-const DOUBLED_PAWN_PENALTY: isize = 10;
-const ISOLATED_PAWN_PENALTY: isize = 20;
-const BACKWARDS_PAWN_PENALTY: isize = 8;
-const PASSED_PAWN_BONUS: isize = 20;
-const ROOK_SEMI_OPEN_FILE_BONUS: isize = 10;
-const ROOK_OPEN_FILE_BONUS: isize = 15;
+fn add_moves(move_list: &mut Vec<Move>, from: usize, targets: u64) {
+    for to in Bits(targets) {
+        move_list.push(Move::new_basic(from, to));
+    }
+}
+
+// Tunable evaluation terms, in centipawns.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EvalParams {
+    pub doubled_pawn_penalty: isize,
+    pub isolated_pawn_penalty: isize,
+    pub backwards_pawn_penalty: isize,
+    pub passed_pawn_bonus: isize,  // per rank advanced
+    pub rook_semi_open_file_bonus: isize,
+    pub rook_open_file_bonus: isize,
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams {
+            doubled_pawn_penalty: 10,
+            isolated_pawn_penalty: 20,
+            backwards_pawn_penalty: 8,
+            passed_pawn_bonus: 20,
+            rook_semi_open_file_bonus: 10,
+            rook_open_file_bonus: 15,
+        }
+    }
+}
 
-fn evaluate_white_pawn(i: usize, white_pawn_rank: &[usize; 10], black_pawn_rank: &[usize; 10]) -> isize {
+fn evaluate_white_pawn(i: usize, white_pawn_rank: &[usize; 10], black_pawn_rank: &[usize; 10],
+                      params: &EvalParams) -> isize {
     let f = i % 8 + 1;
     let r = i / 8;
     let mut s = PAWN_PCSQ[i];
 
     if white_pawn_rank[f] > r {
-        s -= DOUBLED_PAWN_PENALTY;
+        s -= params.doubled_pawn_penalty;
     }
 
     if white_pawn_rank[f - 1] == 0 && white_pawn_rank[f + 1] == 0 {
-        s -= ISOLATED_PAWN_PENALTY;
+        s -= params.isolated_pawn_penalty;
     } else if white_pawn_rank[f - 1] < r && white_pawn_rank[f + 1] < r {
-        s -= BACKWARDS_PAWN_PENALTY;
+        s -= params.backwards_pawn_penalty;
     }
 
     if black_pawn_rank[f - 1] >= r && black_pawn_rank[f] >= r && black_pawn_rank[f + 1] >= r {
-        s += (7 - r as isize) * PASSED_PAWN_BONUS;
+        s += (7 - r as isize) * params.passed_pawn_bonus;
     }
 
     s
 }
 
-fn evaluate_black_pawn(i: usize, white_pawn_rank: &[usize; 10], black_pawn_rank: &[usize; 10]) -> isize {
+fn evaluate_black_pawn(i: usize, white_pawn_rank: &[usize; 10], black_pawn_rank: &[usize; 10],
+                      params: &EvalParams) -> isize {
     let f = i % 8 + 1;
     let r = i / 8;
     let mut s = PAWN_PCSQ[FLIP[i]];
 
     if black_pawn_rank[f] < r {
-        s -= DOUBLED_PAWN_PENALTY;
+        s -= params.doubled_pawn_penalty;
     }
 
     if black_pawn_rank[f - 1] == 7 && black_pawn_rank[f + 1] == 7 {
-        s -= ISOLATED_PAWN_PENALTY;
+        s -= params.isolated_pawn_penalty;
     } else if black_pawn_rank[f - 1] > r && black_pawn_rank[f + 1] > r {
-        s -= BACKWARDS_PAWN_PENALTY;
+        s -= params.backwards_pawn_penalty;
     }
 
     if white_pawn_rank[f - 1] <= r && white_pawn_rank[f] <= r && white_pawn_rank[f + 1] <= r {
-        s += r as isize * PASSED_PAWN_BONUS;
+        s += r as isize * params.passed_pawn_bonus;
     }
 
     s
@@ -360,7 +515,7 @@ fn eval_wkp(f: usize, white_pawn_rank: &[usize; 10], black_pawn_rank: &[usize; 1
         _ => {}
     };
 
-	return r;
+	r
 }
 
 fn evaluate_white_king(i: usize, white_pawn_rank: &[usize; 10], black_pawn_rank: &[usize; 10]) -> isize {
@@ -383,7 +538,7 @@ fn evaluate_white_king(i: usize, white_pawn_rank: &[usize; 10], black_pawn_rank:
         }
     }
 
-    return r;
+    r
 }
 
 fn eval_bkp(f: usize, white_pawn_rank: &[usize; 10], black_pawn_rank: &[usize; 10]) -> isize {
@@ -403,7 +558,7 @@ fn eval_bkp(f: usize, white_pawn_rank: &[usize; 10], black_pawn_rank: &[usize; 1
         _ => {}
     };
 
-	return r;
+	r
 }
 
 fn evaluate_black_king(i: usize, white_pawn_rank: &[usize; 10], black_pawn_rank: &[usize; 10]) -> isize {
@@ -426,456 +581,373 @@ fn evaluate_black_king(i: usize, white_pawn_rank: &[usize; 10], black_pawn_rank:
         }
     }
 
-    return r;
+    r
 }
 
 impl Game {
 
     fn new() -> Game {
-        let mut rng = rand::thread_rng();
         Game {
-            board: {
-                let mut b: [usize; 120] = [OFF_BOARD; 120];
-                for i in 0..64 { b[MAP8X8[i]] = EMPTY; }
-                b
-            },
+            board: [EMPTY; 64],
+            pieces: [0; 7],
+            colors: [0; 2],
             state: 0,
-            king_white: 0,
-            king_black: 0,
-            piece_hashes: {
-                let mut h: [u64; 12 * 64] = [0; 12 * 64];
-                for k in 0..(12 * 64) { h[k] = rng.next_u64(); }
-                h
-            },
-            black_hash: rng.next_u64(),
-            castling_hashes: {
-                let mut h: [u64; 16] = [0; 16];
-                for k in 0..16 { h[k] = rng.next_u64(); }
-                h
-            },
-            ep_hashes: {
-                let mut h: [u64; 8] = [0; 8];
-                for k in 0..8 { h[k] = rng.next_u64(); }
-                h
-            },
             hash: 0,
             history: Vec::new(),
+            start_ply: 0,
+            eval_params: EvalParams::default(),
         }
     }
 
-    pub fn from_fen(fen: &str) -> Result<Game, &str> {
-        let mut iter = fen.split_whitespace();
-        let mut game = Game::new();
-        match iter.next() {
-            Some(s) => {
-                let mut pos: usize = 0;
-                for c in s.chars() {
-                    if c == '/' {
-                        continue;
-                    }
-                    match c.to_digit(10) {
-                        Option::Some(n) => {
-                            pos += n as usize;
-                            continue;
-                        },
-                        Option::None => {}
-                    };
-                    match PIECE_ASCII.find(c) {
-                        Option::Some(idx) => {
-                            game.board[MAP8X8[pos]] = PIECE_VALUES[idx];
-                            pos += 1;
-                        },
-                        Option::None => return Result::Err("Illegal FEN character")
-                    };
-                }
-            },
-            None => return Result::Err("Empty FEN")
-        };
-        let side = match iter.next() {
-            Some(s) => match &s.to_lowercase()[..] {
-                "w" => WHITE,
-                "b" => BLACK,
-                _ => return Result::Err("Illegal side string")
-            },
-            None => return Result::Err("Missing side")
-        };
-        let mut castling: usize = 0;
-        match iter.next() {
-            Some(s) => {
-                for c in s.chars() {
-                    match c {
-                        'K' => castling = castling | CASTLING_KING_WHITE,
-                        'Q' => castling = castling | CASTLING_QUEEN_WHITE,
-                        'k' => castling = castling | CASTLING_KING_BLACK,
-                        'q' => castling = castling | CASTLING_QUEEN_BLACK,
-                        '-' => continue,
-                        _ => return Result::Err("Illegal castling character")
-                    }
-                }
-            },
-            None => {}
-        };
-        let ep = match iter.next() {
-            Some(s) => match s {
-                "-" => 0,
-                _ => match algebraic_to_pos(s) {
-                    Some(p) => MAP8X8[p],
-                    None => return Result::Err("Illegal en passant string")
-                }
-            },
-            None => 0
-        };
-        let draw_ply = match iter.next() {
-            Some(s) => match s.parse::<usize>() {
-                Ok(v) => {
-                    if v >= 100 {
-                        return Result::Err("Illegal ply value"); 
-                    }
-                    v
-                },
-                Err(_) => return Result::Err("Illegal ply format")
-            },
-            None => 0
-        };
-        game.state = draw_ply << 24 | ep << 16 | castling << 8 | side;
-        game.king_white = match game.board.iter().position(|&p| p == WHITE | KING) {
-            Some(i) => i,
-            None => return Result::Err("No white king")
-        };
-        game.king_black = match game.board.iter().position(|&p| p == BLACK | KING) {
-            Some(i) => i,
-            None => return Result::Err("No black king")
-        };
-        game.set_hash();
-        return Result::Ok(game);
+    fn put_piece(self: &mut Game, pos: usize, piece: usize) {
+        let bit = 1u64 << pos;
+        self.board[pos] = piece;
+        self.pieces[piece & PIECE_MASK] |= bit;
+        self.colors[color_index(piece & COLOR_MASK)] |= bit;
     }
 
-    fn set_hash(self: &mut Game) {
-        let mut hash: u64 = 0;
-        for i in 0..64 {
-            let piece = self.board[MAP8X8[i]];
-            if piece != EMPTY {
-                let mut n = (piece & PIECE_MASK) - 1;
-                if (piece & COLOR_MASK) == BLACK {
-                    n += 6;
-                }
-                hash ^= self.piece_hashes[n * 64 + i];
-            }
+    fn remove_piece(self: &mut Game, pos: usize) {
+        let piece = self.board[pos];
+        let bit = 1u64 << pos;
+        self.board[pos] = EMPTY;
+        self.pieces[piece & PIECE_MASK] &= !bit;
+        self.colors[color_index(piece & COLOR_MASK)] &= !bit;
+    }
+
+    fn occupied(self: &Game) -> u64 {
+        self.colors[0] | self.colors[1]
+    }
+
+    fn pieces_of(self: &Game, piece: usize) -> u64 {
+        self.pieces[piece & PIECE_MASK] & self.colors[color_index(piece & COLOR_MASK)]
+    }
+
+    fn king_position(self: &Game, color: usize) -> usize {
+        lowest_square(self.pieces_of(KING | color))
+    }
+
+    fn piece_hash(self: &Game, piece: usize, pos: usize) -> u64 {
+        let mut n = 2 * POLYGLOT_KIND[piece & PIECE_MASK];
+        if (piece & COLOR_MASK) == WHITE {
+            n += 1;
+        }
+        ZOBRIST_KEYS[n * 64 + (pos ^ 56)]
+    }
+
+    // As in Polyglot, the en passant file only counts when a pawn of the side
+    // to move can actually make the capture.
+    fn ep_hash(self: &Game, ep: usize, side: usize) -> u64 {
+        if ep == 0 {
+            return 0;
         }
-        if !self.white_to_move() {
-            hash ^= self.black_hash;
+        let capturers = PAWN_ATTACKS[color_index(side) ^ 1][ep] & self.pieces_of(PAWN | side);
+        if capturers != 0 { ZOBRIST_KEYS[ZOBRIST_EP + ep % 8] } else { 0 }
+    }
+
+    fn compute_hash(self: &Game) -> u64 {
+        let mut hash: u64 = 0;
+        for pos in Bits(self.occupied()) {
+            hash ^= self.piece_hash(self.board[pos], pos);
         }
-        hash ^= self.castling_hashes[(self.state >> 8) & 15];
-        let ep = self.state.ep();
-        if ep != 0 {
-            hash ^= self.ep_hashes[(ep % 10) - 1];
+        if self.white_to_move() {
+            hash ^= ZOBRIST_KEYS[ZOBRIST_WHITE];
         }
-        self.hash = hash;
+        hash ^= castling_hash((self.state >> 8) & 15);
+        hash ^= self.ep_hash(self.state.ep(), self.state & 0xff);
+        hash
+    }
+
+    pub fn hash(self: &Game) -> u64 {
+        self.hash
     }
 
     fn is_attacked_by(self: &Game, pos: usize, color: usize) -> bool {
-        if color == WHITE {
-            if self.board[pos + 9] == (PAWN | WHITE) || self.board[pos + 11] == (PAWN | WHITE) {
-                return true;
-            }
-        } else {
-            if self.board[pos - 9] == (PAWN | BLACK) || self.board[pos - 11] == (PAWN | BLACK) {
-                return true;
-            }
+        let occupied = self.occupied();
+        let them = self.colors[color_index(color)];
+        PAWN_ATTACKS[color_index(color) ^ 1][pos] & self.pieces[PAWN] & them != 0
+            || KNIGHT_ATTACKS[pos] & self.pieces[KNIGHT] & them != 0
+            || KING_ATTACKS[pos] & self.pieces[KING] & them != 0
+            || bishop_attacks(pos, occupied) & (self.pieces[BISHOP] | self.pieces[QUEEN]) & them != 0
+            || rook_attacks(pos, occupied) & (self.pieces[ROOK] | self.pieces[QUEEN]) & them != 0
+    }
+
+    fn attackers(self: &Game, pos: usize, occupied: u64) -> u64 {
+        PAWN_ATTACKS[1][pos] & self.pieces_of(WHITE_PAWN)
+            | PAWN_ATTACKS[0][pos] & self.pieces_of(BLACK_PAWN)
+            | KNIGHT_ATTACKS[pos] & self.pieces[KNIGHT]
+            | KING_ATTACKS[pos] & self.pieces[KING]
+            | bishop_attacks(pos, occupied) & (self.pieces[BISHOP] | self.pieces[QUEEN])
+            | rook_attacks(pos, occupied) & (self.pieces[ROOK] | self.pieces[QUEEN])
+    }
+
+    // Static exchange evaluation: the material won by `mv` when both sides
+    // go on capturing on its target square with their least valuable piece,
+    // each free to stop when that is better. Pieces lined up behind others
+    // (x-rays) join as the line opens; pins are not considered.
+    pub fn see(self: &Game, mv: Move) -> isize {
+        let from = mv.from().index();
+        let to = mv.to().index();
+        let piece = self.board[from];
+        let mut occupied = self.occupied() & !(1 << from);
+        let mut gain = [0isize; 32];
+        gain[0] = EXCHANGE_VALUES[self.board[to] & PIECE_MASK];
+        if piece & PIECE_MASK == PAWN && to == self.state.ep() && self.board[to] == EMPTY {
+            gain[0] = PAWN_VALUE;
+            occupied &= !(1 << (if piece == WHITE_PAWN { to + 8 } else { to - 8 }));
         }
-        for delta in BISHOP_MOVEMENTS.iter() {
-            let mut to = ((pos as isize) + delta) as usize;
-            while self.board[to] == EMPTY {
-                to = ((to as isize) + delta) as usize;
-            }
-            if self.board[to] == BISHOP | color {
-                return true;
-            }
+        let mut on_square = EXCHANGE_VALUES[piece & PIECE_MASK];
+        if mv.promoted() != EMPTY {
+            gain[0] += EXCHANGE_VALUES[mv.promoted()] - PAWN_VALUE;
+            on_square = EXCHANGE_VALUES[mv.promoted()];
         }
-        for delta in KNIGHT_MOVEMENTS.iter() {
-            if self.board[((pos as isize) + delta) as usize] == KNIGHT | color {
-                return true;
+        let mut color = color_index(piece & COLOR_MASK) ^ 1;
+        let mut d = 0;
+        loop {
+            let attackers = self.attackers(to, occupied) & occupied & self.colors[color];
+            let kind = match [PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING].iter()
+                                 .find(|&&kind| attackers & self.pieces[kind] != 0) {
+                Some(&kind) => kind,
+                None => break,
+            };
+            let bit = 1u64 << lowest_square(attackers & self.pieces[kind]);
+            // the king cannot capture a defended piece
+            if kind == KING && self.attackers(to, occupied & !bit) & occupied & self.colors[color ^ 1] != 0 {
+                break;
             }
+            d += 1;
+            gain[d] = on_square - gain[d - 1];
+            occupied &= !bit;
+            on_square = EXCHANGE_VALUES[kind];
+            color ^= 1;
         }
-        for delta in ROOK_MOVEMENTS.iter() {
-            let mut to = ((pos as isize) + delta) as usize;
-            while self.board[to] == EMPTY {
-                to = ((to as isize) + delta) as usize;
-            }
-            if self.board[to] == ROOK | color {
-                return true;
-            }
+        while d > 0 {
+            gain[d - 1] = -std::cmp::max(-gain[d - 1], gain[d]);
+            d -= 1;
         }
-        for delta in KING_MOVEMENTS.iter() {
-            let mut to = ((pos as isize) + delta) as usize;
-            while self.board[to] == EMPTY {
-                to = ((to as isize) + delta) as usize;
+        gain[0]
+    }
+
+    // Pawn moves (captures only unless `quiet`) except en passant, restricted
+    // to `targets` and per pawn to `pins`.
+    fn pawn_moves(self: &Game, move_list: &mut Vec<Move>, quiet: bool, targets: u64, pins: &[u64; 64]) {
+        let side = self.state & 0xff;
+        let us = color_index(side);
+        let empty = !self.occupied();
+        let them = self.colors[us ^ 1];
+
+        for from in Bits(self.pieces_of(PAWN | side)) {
+            let mut to_bits = PAWN_ATTACKS[us][from] & them;
+            if quiet {
+                let (push, double, start) = if side == WHITE {
+                    (from - 8, from.wrapping_sub(16), from >= 48)
+                } else {
+                    (from + 8, from + 16, from < 16)
+                };
+                if empty & (1 << push) != 0 {
+                    to_bits |= 1 << push;
+                    if start && empty & (1 << double) != 0 {
+                        to_bits |= 1 << double;
+                    }
+                }
             }
-            if self.board[to] == QUEEN | color {
-                return true;
+            let promoting = if side == WHITE { from < 16 } else { from >= 48 };
+            for to in Bits(to_bits & targets & pins[from]) {
+                if promoting {
+                    add_promotion(move_list, from, to);
+                } else {
+                    add_move(move_list, from, to);
+                }
             }
         }
-        for delta in KING_MOVEMENTS.iter() {
-            if self.board[((pos as isize) + delta) as usize] == KING | color {
-                return true;
+    }
+
+    fn ep_moves(self: &Game, move_list: &mut Vec<Move>, legal: bool) {
+        let ep = self.state.ep();
+        if ep == 0 {
+            return;
+        }
+        let side = self.state & 0xff;
+        let us = color_index(side);
+        let captured = if side == WHITE { ep + 8 } else { ep - 8 };
+        let king = self.king_position(side);
+        for from in Bits(PAWN_ATTACKS[us ^ 1][ep] & self.pieces_of(PAWN | side)) {
+            // two pawns leave the board at once, so test the resulting position
+            if legal {
+                let occupied = (self.occupied() ^ (1 << from) ^ (1 << captured)) | (1 << ep);
+                if self.attackers(king, occupied) & self.colors[us ^ 1] & !(1 << captured) != 0 {
+                    continue;
+                }
             }
+            add_move(move_list, from, ep);
         }
-        return false;
     }
 
-    pub fn generate_moves(self: &Game) -> Vec<Move> {
+    // Knight, bishop, rook and queen moves
+    fn piece_moves(self: &Game, move_list: &mut Vec<Move>, targets: u64, pins: &[u64; 64]) {
         let side = self.state & 0xff;
-        let xside = if side == WHITE { BLACK } else { WHITE };
-        let castling = (self.state >> 8) & 0xff;
-        let ep = self.state.ep();
+        let occupied = self.occupied();
+        for from in Bits(self.pieces_of(KNIGHT | side)) {
+            add_moves(move_list, from, KNIGHT_ATTACKS[from] & targets & pins[from]);
+        }
+        for from in Bits(self.pieces_of(BISHOP | side)) {
+            add_moves(move_list, from, bishop_attacks(from, occupied) & targets & pins[from]);
+        }
+        for from in Bits(self.pieces_of(ROOK | side)) {
+            add_moves(move_list, from, rook_attacks(from, occupied) & targets & pins[from]);
+        }
+        for from in Bits(self.pieces_of(QUEEN | side)) {
+            add_moves(move_list, from, queen_attacks(from, occupied) & targets & pins[from]);
+        }
+    }
 
-        let mut move_list = Vec::with_capacity(218);
-        for i in 0..64 {
-            let from = MAP8X8[i];
-            let piece = self.board[from];
-            if piece & COLOR_MASK == side {
-                match piece & PIECE_MASK {
-                    PAWN if side == WHITE => {
-                        if i >> 3 == 1 {
-                            if self.board[from - 10] == EMPTY {
-                                add_promotion(&mut move_list, from, from - 10);
-                            }
-                            if self.board[from - 11] & COLOR_MASK == BLACK {
-                                add_promotion(&mut move_list, from, from - 11);
-                            }
-                            if self.board[from - 9] & COLOR_MASK == BLACK {
-                                add_promotion(&mut move_list, from, from - 9);
-                            }
-                        } else {
-                            if self.board[from - 10] == EMPTY {
-                                add_move(&mut move_list, from, from - 10);
-                                if i >> 3 == 6 && self.board[from - 20] == EMPTY {
-                                    add_move(&mut move_list, from, from - 20);
-                                }
-                            }
-                            if self.board[from - 11] & COLOR_MASK == BLACK || from - 11 == ep {
-                                add_move(&mut move_list, from, from - 11);
-                            }
-                            if self.board[from - 9] & COLOR_MASK == BLACK || from - 9 == ep {
-                                add_move(&mut move_list, from, from - 9);
-                            }
-                        }
-                    },
-                    PAWN if side == BLACK => {
-                        if i >> 3 == 6 {
-                            if self.board[from + 10] == EMPTY {
-                                add_promotion(&mut move_list, from, from + 10);
-                            }
-                            if self.board[from + 11] & COLOR_MASK == WHITE {
-                                add_promotion(&mut move_list, from, from + 11);
-                            }
-                            if self.board[from + 9] & COLOR_MASK == WHITE {
-                                add_promotion(&mut move_list, from, from + 9);
-                            }
-                        } else {
-                            if self.board[from + 10] == EMPTY {
-                                add_move(&mut move_list, from, from + 10);
-                                if i >> 3 == 1 && self.board[from + 20] == EMPTY {
-                                    add_move(&mut move_list, from, from + 20);
-                                }
-                            }
-                            if self.board[from + 11] & COLOR_MASK == WHITE || from + 11 == ep {
-                                add_move(&mut move_list, from, from + 11);
-                            }
-                            if self.board[from + 9] & COLOR_MASK == WHITE || from + 9 == ep {
-                                add_move(&mut move_list, from, from + 9);
-                            }
-                        }
-                    },
-                    BISHOP => {
-                        for delta in BISHOP_MOVEMENTS.iter() {
-                            let mut to = ((from as isize) + delta) as usize;
-                            while self.board[to] == EMPTY {
-                                add_move(&mut move_list, from, to);
-                                to = ((to as isize) + delta) as usize;
-                            }
-                            if self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    KNIGHT => {
-                        for delta in KNIGHT_MOVEMENTS.iter() {
-                            let to = ((from as isize) + delta) as usize;
-                            if self.board[to] == EMPTY || self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    ROOK => {
-                        for delta in ROOK_MOVEMENTS.iter() {
-                            let mut to = ((from as isize) + delta) as usize;
-                            while self.board[to] == EMPTY {
-                                add_move(&mut move_list, from, to);
-                                to = ((to as isize) + delta) as usize;
-                            }
-                            if self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    QUEEN => {
-                        for delta in KING_MOVEMENTS.iter() {
-                            let mut to = ((from as isize) + delta) as usize;
-                            while self.board[to] == EMPTY {
-                                add_move(&mut move_list, from, to);
-                                to = ((to as isize) + delta) as usize;
-                            }
-                            if self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    KING => {
-                        if from == 95 && (castling & (CASTLING_QUEEN_WHITE | CASTLING_KING_WHITE)) != 0
-                                && !self.is_attacked_by(95, BLACK) {
-                            if (castling & CASTLING_QUEEN_WHITE) != 0
-                                    && self.board[94] == EMPTY && self.board[93] == EMPTY && self.board[92] == EMPTY
-                                    && !self.is_attacked_by(94, BLACK) {
-                                add_move(&mut move_list, 95, 93);
-                            }
-                            if (castling & CASTLING_KING_WHITE) != 0
-                                    && self.board[96] == EMPTY && self.board[97] == EMPTY
-                                    && !self.is_attacked_by(96, BLACK) {
-                                add_move(&mut move_list, 95, 97);
-                            }
-                        } else if from == 25 && (castling & (CASTLING_QUEEN_BLACK | CASTLING_KING_BLACK)) != 0
-                                && !self.is_attacked_by(25, WHITE) {
-                            if (castling & CASTLING_QUEEN_BLACK) != 0
-                                    && self.board[24] == EMPTY && self.board[23] == EMPTY && self.board[22] == EMPTY
-                                    && !self.is_attacked_by(24, WHITE) {
-                                add_move(&mut move_list, 25, 23);
-                            }
-                            if (castling & CASTLING_KING_BLACK) != 0
-                                    && self.board[26] == EMPTY && self.board[27] == EMPTY
-                                    && !self.is_attacked_by(26, WHITE) {
-                                add_move(&mut move_list, 25, 27);
-                            }
-                        }
-                        for delta in KING_MOVEMENTS.iter() {
-                            let to = ((from as isize) + delta) as usize;
-                            if self.board[to] == EMPTY || self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    _ => panic!("generate_moves: unknown piece")
+    fn castling_moves(self: &Game, move_list: &mut Vec<Move>) {
+        let side = self.state & 0xff;
+        let castling = (self.state >> 8) & 0xff;
+        let occupied = self.occupied();
+        if side == WHITE {
+            if self.board[60] == WHITE_KING && (castling & (CASTLING_QUEEN_WHITE | CASTLING_KING_WHITE)) != 0
+                    && !self.is_attacked_by(60, BLACK) {
+                if (castling & CASTLING_QUEEN_WHITE) != 0 && occupied & 0x0e00_0000_0000_0000 == 0
+                        && !self.is_attacked_by(59, BLACK) && !self.is_attacked_by(58, BLACK) {
+                    add_move(move_list, 60, 58);
+                }
+                if (castling & CASTLING_KING_WHITE) != 0 && occupied & 0x6000_0000_0000_0000 == 0
+                        && !self.is_attacked_by(61, BLACK) && !self.is_attacked_by(62, BLACK) {
+                    add_move(move_list, 60, 62);
                 }
             }
+        } else if self.board[4] == BLACK_KING && (castling & (CASTLING_QUEEN_BLACK | CASTLING_KING_BLACK)) != 0
+                && !self.is_attacked_by(4, WHITE) {
+            if (castling & CASTLING_QUEEN_BLACK) != 0 && occupied & 0x0e == 0
+                    && !self.is_attacked_by(3, WHITE) && !self.is_attacked_by(2, WHITE) {
+                add_move(move_list, 4, 2);
+            }
+            if (castling & CASTLING_KING_BLACK) != 0 && occupied & 0x60 == 0
+                    && !self.is_attacked_by(5, WHITE) && !self.is_attacked_by(6, WHITE) {
+                add_move(move_list, 4, 6);
+            }
         }
+    }
+
+    // Pseudo-legal moves; make_move rejects the ones leaving the king in check.
+    pub fn generate_moves(self: &Game) -> Vec<Move> {
+        let side = self.state & 0xff;
+        let targets = !self.colors[color_index(side)];
+        let king = self.king_position(side);
+        let mut move_list = Vec::with_capacity(218);
+        self.pawn_moves(&mut move_list, true, targets, &NO_PINS);
+        self.ep_moves(&mut move_list, false);
+        self.piece_moves(&mut move_list, targets, &NO_PINS);
+        add_moves(&mut move_list, king, KING_ATTACKS[king] & targets);
+        self.castling_moves(&mut move_list);
         move_list
     }
 
     pub fn capture_moves(self: &Game) -> Vec<Move> {
         let side = self.state & 0xff;
-        let xside = if side == WHITE { BLACK } else { WHITE };
-        let ep = self.state.ep();
+        let targets = self.colors[color_index(side) ^ 1];
+        let king = self.king_position(side);
+        let mut move_list = Vec::with_capacity(64);
+        self.pawn_moves(&mut move_list, false, targets, &NO_PINS);
+        self.ep_moves(&mut move_list, false);
+        self.piece_moves(&mut move_list, targets, &NO_PINS);
+        add_moves(&mut move_list, king, KING_ATTACKS[king] & targets);
+        move_list
+    }
 
-        let mut move_list = Vec::with_capacity(218);
-        for i in 0..64 {
-            let from = MAP8X8[i];
-            let piece = self.board[from];
-            if piece & COLOR_MASK == side {
-                match piece & PIECE_MASK {
-                    PAWN if side == WHITE => {
-                        if i >> 3 == 1 {
-                            if self.board[from - 11] & COLOR_MASK == BLACK {
-                                add_promotion(&mut move_list, from, from - 11);
-                            }
-                            if self.board[from - 9] & COLOR_MASK == BLACK {
-                                add_promotion(&mut move_list, from, from - 9);
-                            }
-                        } else {
-                            if self.board[from - 11] & COLOR_MASK == BLACK || from - 11 == ep {
-                                add_move(&mut move_list, from, from - 11);
-                            }
-                            if self.board[from - 9] & COLOR_MASK == BLACK || from - 9 == ep {
-                                add_move(&mut move_list, from, from - 9);
-                            }
-                        }
-                    },
-                    PAWN if side == BLACK => {
-                        if i >> 3 == 6 {
-                            if self.board[from + 11] & COLOR_MASK == WHITE {
-                                add_promotion(&mut move_list, from, from + 11);
-                            }
-                            if self.board[from + 9] & COLOR_MASK == WHITE {
-                                add_promotion(&mut move_list, from, from + 9);
-                            }
-                        } else {
-                            if self.board[from + 11] & COLOR_MASK == WHITE || from + 11 == ep {
-                                add_move(&mut move_list, from, from + 11);
-                            }
-                            if self.board[from + 9] & COLOR_MASK == WHITE || from + 9 == ep {
-                                add_move(&mut move_list, from, from + 9);
-                            }
-                        }
-                    },
-                    BISHOP => {
-                        for delta in BISHOP_MOVEMENTS.iter() {
-                            let mut to = ((from as isize) + delta) as usize;
-                            while self.board[to] == EMPTY {
-                                to = ((to as isize) + delta) as usize;
-                            }
-                            if self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    KNIGHT => {
-                        for delta in KNIGHT_MOVEMENTS.iter() {
-                            let to = ((from as isize) + delta) as usize;
-                            if self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    ROOK => {
-                        for delta in ROOK_MOVEMENTS.iter() {
-                            let mut to = ((from as isize) + delta) as usize;
-                            while self.board[to] == EMPTY {
-                                to = ((to as isize) + delta) as usize;
-                            }
-                            if self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    QUEEN => {
-                        for delta in KING_MOVEMENTS.iter() {
-                            let mut to = ((from as isize) + delta) as usize;
-                            while self.board[to] == EMPTY {
-                                to = ((to as isize) + delta) as usize;
-                            }
-                            if self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    KING => {
-                        for delta in KING_MOVEMENTS.iter() {
-                            let to = ((from as isize) + delta) as usize;
-                            if self.board[to] & COLOR_MASK == xside {
-                                add_move(&mut move_list, from, to);
-                            }
-                        }
-                    },
-                    _ => panic!("capture_moves: unknown piece")
+    // The moves of generate_moves() that capture nothing.
+    fn quiet_moves(self: &Game) -> Vec<Move> {
+        let side = self.state & 0xff;
+        let targets = !self.occupied();
+        let king = self.king_position(side);
+        let mut move_list = Vec::with_capacity(128);
+        self.pawn_moves(&mut move_list, true, targets, &NO_PINS);
+        self.piece_moves(&mut move_list, targets, &NO_PINS);
+        add_moves(&mut move_list, king, KING_ATTACKS[king] & targets);
+        self.castling_moves(&mut move_list);
+        move_list
+    }
+
+    // Whether generate_moves() would include `mv`, for moves that come from
+    // elsewhere (the transposition table, killer slots) and may not fit.
+    fn is_pseudo_legal(self: &Game, mv: Move) -> bool {
+        let from = mv.from().index();
+        let to = mv.to().index();
+        let side = self.state & 0xff;
+        let piece = self.board[from];
+        if piece & COLOR_MASK != side || self.board[to] & COLOR_MASK == side {
+            return false;
+        }
+        let occupied = self.occupied();
+        let targets = match piece & PIECE_MASK {
+            PAWN | KING => {
+                let mut move_list = Vec::new();
+                if piece & PIECE_MASK == PAWN {
+                    self.pawn_moves(&mut move_list, true, !self.colors[color_index(side)], &NO_PINS);
+                    self.ep_moves(&mut move_list, false);
+                } else {
+                    add_moves(&mut move_list, from, KING_ATTACKS[from] & !self.colors[color_index(side)]);
+                    self.castling_moves(&mut move_list);
                 }
+                return move_list.contains(&mv);
+            },
+            KNIGHT => KNIGHT_ATTACKS[from],
+            BISHOP => bishop_attacks(from, occupied),
+            ROOK => rook_attacks(from, occupied),
+            _ => queen_attacks(from, occupied),
+        };
+        mv.promoted() == EMPTY && targets & (1 << to) != 0
+    }
+
+    // Strictly legal moves, found from the checkers and pinned pieces up front.
+    pub fn legal_moves(self: &Game) -> Vec<Move> {
+        let side = self.state & 0xff;
+        let us = color_index(side);
+        let own = self.colors[us];
+        let them = self.colors[us ^ 1];
+        let occupied = own | them;
+        let king = self.king_position(side);
+        let mut move_list = Vec::with_capacity(218);
+
+        // the king is taken off the board so it cannot shield a square on a checking line
+        for to in Bits(KING_ATTACKS[king] & !own) {
+            if self.attackers(to, occupied ^ (1 << king)) & them == 0 {
+                add_move(&mut move_list, king, to);
             }
         }
+
+        let checkers = self.attackers(king, occupied) & them;
+        if checkers.count_ones() > 1 {
+            return move_list;
+        }
+        let targets = if checkers != 0 {
+            between(king, lowest_square(checkers)) | checkers
+        } else {
+            self.castling_moves(&mut move_list);
+            !own
+        };
+
+        let mut pins = NO_PINS;
+        let snipers = (rook_attacks(king, 0) & (self.pieces[ROOK] | self.pieces[QUEEN])
+                       | bishop_attacks(king, 0) & (self.pieces[BISHOP] | self.pieces[QUEEN])) & them;
+        for sniper in Bits(snipers) {
+            let line = between(king, sniper);
+            let blockers = line & occupied;
+            if blockers.count_ones() == 1 && blockers & own != 0 {
+                pins[lowest_square(blockers)] = line | 1 << sniper;
+            }
+        }
+
+        self.pawn_moves(&mut move_list, true, targets, &pins);
+        self.ep_moves(&mut move_list, true);
+        self.piece_moves(&mut move_list, targets, &pins);
         move_list
     }
 
     pub fn make_move(self: &mut Game, mv: Move) -> bool {
-        let from = mv.from();
-        let to = mv.to();
-        let promoted = mv.promotion();
+        let from = mv.from().index();
+        let to = mv.to().index();
+        let promoted = mv.promoted();
         let piece = self.board[from];
         let captured = self.board[to];
         let from_state = self.state;
@@ -887,59 +959,72 @@ impl Game {
         let to_castling = from_castling & CASTLE_MASK[from] & CASTLE_MASK[to];
         let side = from_state & 0xff;
         let xside = if side == WHITE { BLACK } else { WHITE };
+        let moved = if promoted != EMPTY { promoted | side } else { piece };
+        let mut hash = self.hash ^ ZOBRIST_KEYS[ZOBRIST_WHITE] ^ self.ep_hash(from_ep, side)
+                       ^ self.piece_hash(piece, from) ^ self.piece_hash(moved, to);
 
-        self.board[to] = if promoted != EMPTY { promoted | side } else { piece };
-        self.board[from] = EMPTY;
-
-        if piece == PAWN | WHITE {
-            if to == from_ep {
-                self.board[to + 10] = EMPTY;
-            } else if to == from - 20 {
-                to_ep = from - 10;
+        if captured != EMPTY {
+            hash ^= self.piece_hash(captured, to);
+            self.remove_piece(to);
+        }
+        self.remove_piece(from);
+        self.put_piece(to, moved);
+
+        if piece == WHITE_PAWN {
+            if from_ep != 0 && to == from_ep {
+                self.remove_piece(to + 8);
+                hash ^= self.piece_hash(BLACK_PAWN, to + 8);
+            } else if to + 16 == from {
+                to_ep = from - 8;
             }
             to_draw_ply = 0;
-        } else if piece == PAWN | BLACK {
-            if to == from_ep {
-                self.board[to - 10] = EMPTY;
-            } else if to == from + 20 {
-                to_ep = from + 10;
+        } else if piece == BLACK_PAWN {
+            if from_ep != 0 && to == from_ep {
+                self.remove_piece(to - 8);
+                hash ^= self.piece_hash(WHITE_PAWN, to - 8);
+            } else if to == from + 16 {
+                to_ep = from + 8;
             }
             to_draw_ply = 0;
-        } else if piece == KING | WHITE {
-            self.king_white = to;
-            if from == 95 {
-                if to == 93 {
-                    self.board[91] = EMPTY;
-                    self.board[94] = ROOK | WHITE;
-                } else if to == 97 {
-                    self.board[98] = EMPTY;
-                    self.board[96] = ROOK | WHITE;
-                }
+        } else if piece == WHITE_KING && from == 60 {
+            if to == 58 {
+                self.remove_piece(56);
+                self.put_piece(59, WHITE_ROOK);
+                hash ^= self.piece_hash(WHITE_ROOK, 56) ^ self.piece_hash(WHITE_ROOK, 59);
+            } else if to == 62 {
+                self.remove_piece(63);
+                self.put_piece(61, WHITE_ROOK);
+                hash ^= self.piece_hash(WHITE_ROOK, 63) ^ self.piece_hash(WHITE_ROOK, 61);
             }
-        } else if piece == KING | BLACK {
-            self.king_black = to;
-            if from == 25 {
-                if to == 23 {
-                    self.board[21] = EMPTY;
-                    self.board[24] = ROOK | BLACK;
-                } else if to == 27 {
-                    self.board[28] = EMPTY;
-                    self.board[26] = ROOK | BLACK;
-                }
+        } else if piece == BLACK_KING && from == 4 {
+            if to == 2 {
+                self.remove_piece(0);
+                self.put_piece(3, BLACK_ROOK);
+                hash ^= self.piece_hash(BLACK_ROOK, 0) ^ self.piece_hash(BLACK_ROOK, 3);
+            } else if to == 6 {
+                self.remove_piece(7);
+                self.put_piece(5, BLACK_ROOK);
+                hash ^= self.piece_hash(BLACK_ROOK, 7) ^ self.piece_hash(BLACK_ROOK, 5);
             }
         }
 
+        if from_castling != to_castling {
+            hash ^= castling_hash(from_castling ^ to_castling);
+        }
+        hash ^= self.ep_hash(to_ep, xside);
+
         self.state = to_draw_ply << 24 | to_ep << 16 | to_castling << 8 | xside;
         self.history.push(HistoryItem {
-            unmove: (captured as u64) << 32 | from_state as u64,
+            unmove: (from_state as u64) << 8 | captured as u64,
             hash: self.hash
         });
+        self.hash = hash;
 
-        if self.is_attacked_by(if side == WHITE { self.king_white } else { self.king_black }, xside) {
+        if self.is_attacked_by(self.king_position(side), xside) {
             self.unmake_move(mv);
             return false;
         }
-        self.set_hash();
+        debug_assert_eq!(self.hash, self.compute_hash(), "incremental hash out of sync after {}", mv.to_algebraic());
 
         true
     }
@@ -947,66 +1032,176 @@ impl Game {
     pub fn unmake_move(self: &mut Game, mv: Move) {
         let HistoryItem { unmove, hash } = self.history.pop().unwrap();
 
-        let from = mv.from();
-        let to = mv.to();
-        let promoted = mv.promotion();
-        let captured = ((unmove >> 32) & 0xff) as usize;
-        self.state = (unmove & 0xffffffff) as usize;
+        let from = mv.from().index();
+        let to = mv.to().index();
+        let promoted = mv.promoted();
+        let captured = (unmove & 0xff) as usize;
+        self.state = (unmove >> 8) as usize;
         let side = self.state & 0xff;
         let ep = (self.state >> 16) & 0xff;
         let piece = if promoted != EMPTY { PAWN | side } else { self.board[to] };
-        self.board[from] = piece;
-        self.board[to] = captured;
+        self.remove_piece(to);
+        self.put_piece(from, piece);
+        if captured != EMPTY {
+            self.put_piece(to, captured);
+        }
         self.hash = hash;
 
-        if piece == PAWN | WHITE {
-            if to == ep {
-                self.board[to + 10] = PAWN | BLACK;
+        if piece == WHITE_PAWN {
+            if ep != 0 && to == ep {
+                self.put_piece(to + 8, BLACK_PAWN);
             }
-        } else if piece == PAWN | BLACK {
-            if to == ep {
-                self.board[to - 10] = PAWN | WHITE;
+        } else if piece == BLACK_PAWN {
+            if ep != 0 && to == ep {
+                self.put_piece(to - 8, WHITE_PAWN);
             }
-        } else if piece == KING | WHITE {
-            self.king_white = from;
-            if from == 95 && to == 93 {
-                self.board[91] = ROOK | WHITE;
-                self.board[94] = EMPTY;
-            } else if from == 95 && to == 97 {
-                self.board[98] = ROOK | WHITE;
-                self.board[96] = EMPTY;
+        } else if piece == WHITE_KING && from == 60 {
+            if to == 58 {
+                self.remove_piece(59);
+                self.put_piece(56, WHITE_ROOK);
+            } else if to == 62 {
+                self.remove_piece(61);
+                self.put_piece(63, WHITE_ROOK);
             }
-        } else if piece == KING | BLACK {
-            self.king_black = from;
-            if from == 25 && to == 23 {
-                self.board[21] = ROOK | BLACK;
-                self.board[24] = EMPTY;
-            } else if from == 25 && to == 27 {
-                self.board[28] = ROOK | BLACK;
-                self.board[26] = EMPTY;
+        } else if piece == BLACK_KING && from == 4 {
+            if to == 2 {
+                self.remove_piece(3);
+                self.put_piece(0, BLACK_ROOK);
+            } else if to == 6 {
+                self.remove_piece(5);
+                self.put_piece(7, BLACK_ROOK);
             }
         }
     }
 
-    pub fn score_moves(self: &Game, move_list: &mut Vec<Move>, cutoff_moves: &[usize; 64 * 64], top_move: Move) {
-        let tm = top_move & 0xffffffff;
-        for mv in move_list.iter_mut() {
-            let m = *mv & 0xffffffff;
-            let score: usize = if m == tm {
-                1000000000
+    // Passes the turn, for null-move pruning. The draw ply count restarts so
+    // that repetitions are not detected across the null move.
+    pub fn make_null_move(self: &mut Game) {
+        let from_state = self.state;
+        let side = from_state & 0xff;
+        let xside = if side == WHITE { BLACK } else { WHITE };
+        let castling = (from_state >> 8) & 0xff;
+        self.state = castling << 8 | xside;
+        self.history.push(HistoryItem {
+            unmove: (from_state as u64) << 8 | EMPTY as u64,
+            hash: self.hash
+        });
+        self.hash ^= ZOBRIST_KEYS[ZOBRIST_WHITE] ^ self.ep_hash(from_state.ep(), side);
+        debug_assert_eq!(self.hash, self.compute_hash(), "incremental hash out of sync after null move");
+    }
+
+    pub fn unmake_null_move(self: &mut Game) {
+        let HistoryItem { unmove, hash } = self.history.pop().unwrap();
+        self.state = (unmove >> 8) as usize;
+        self.hash = hash;
+    }
+
+    // Neither a capture (en passant included) nor a promotion.
+    fn is_quiet(self: &Game, mv: Move) -> bool {
+        let to = mv.to().index();
+        self.board[to] == EMPTY && mv.promoted() == EMPTY
+            && !(self.board[mv.from().index()] & PIECE_MASK == PAWN && to == self.state.ep())
+    }
+
+    // The material won by a capture or promotion, not counting recaptures.
+    fn material_gain(self: &Game, mv: Move) -> isize {
+        let to = mv.to().index();
+        let mut gain = EXCHANGE_VALUES[self.board[to] & PIECE_MASK];
+        if self.board[mv.from().index()] & PIECE_MASK == PAWN && to == self.state.ep() && self.board[to] == EMPTY {
+            gain = PAWN_VALUE;
+        }
+        if mv.promoted() != EMPTY {
+            gain += EXCHANGE_VALUES[mv.promoted()] - PAWN_VALUE;
+        }
+        gain
+    }
+
+    // Whether `mv` (pseudo-legal) checks the opponent king, directly or by
+    // uncovering a line, without making it.
+    fn gives_check(self: &Game, mv: Move) -> bool {
+        let from = mv.from().index();
+        let to = mv.to().index();
+        let side = self.state & 0xff;
+        let us = color_index(side);
+        let king = self.king_position(side ^ COLOR_MASK);
+        let mut piece = self.board[from] & PIECE_MASK;
+        let mut occupied = (self.occupied() & !(1 << from)) | (1 << to);
+        let mut own = (self.colors[us] & !(1 << from)) | (1 << to);
+        let mut checker = to;
+        if piece == PAWN && to == self.state.ep() && self.board[to] == EMPTY {
+            occupied &= !(1 << (if side == WHITE { to + 8 } else { to - 8 }));
+        }
+        if piece == KING && (from as isize - to as isize).abs() == 2 {
+            // castling checks with the rook
+            let rook_from = if to > from { from + 3 } else { from - 4 };
+            checker = (from + to) / 2;
+            occupied ^= (1 << rook_from) | (1 << checker);
+            own ^= (1 << rook_from) | (1 << checker);
+            piece = ROOK;
+        }
+        if mv.promoted() != EMPTY {
+            piece = mv.promoted();
+        }
+        let direct = match piece {
+            PAWN => PAWN_ATTACKS[us][checker],
+            KNIGHT => KNIGHT_ATTACKS[checker],
+            BISHOP => bishop_attacks(checker, occupied),
+            ROOK => rook_attacks(checker, occupied),
+            QUEEN => queen_attacks(checker, occupied),
+            _ => 0,
+        };
+        let discovered = (bishop_attacks(king, occupied) & (self.pieces[BISHOP] | self.pieces[QUEEN])
+                          | rook_attacks(king, occupied) & (self.pieces[ROOK] | self.pieces[QUEEN]))
+                         & own & !(1 << checker);
+        (direct & (1 << king)) != 0 || discovered != 0
+    }
+
+    // Whether the side to move has pieces other than pawns; without them
+    // zugzwang is common and null-move pruning is unsafe.
+    fn has_non_pawn_material(self: &Game) -> bool {
+        let own = self.colors[color_index(self.state & 0xff)];
+        (self.pieces[KNIGHT] | self.pieces[BISHOP] | self.pieces[ROOK] | self.pieces[QUEEN]) & own != 0
+    }
+
+    // Orders the principal variation and hash moves first, then captures that
+    // do not lose material by static exchange evaluation, killers, the
+    // countermove, quiet moves by history and last the losing captures.
+    pub fn score_moves(self: &Game, move_list: &[Move], hints: &OrderingHints) -> Vec<ScoredMove> {
+        let mut scored = Vec::with_capacity(move_list.len());
+        for &mv in move_list.iter() {
+            let score: usize = if mv == hints.pv_move {
+                ORDER_PV
+            } else if mv == hints.hash_move {
+                ORDER_HASH
             } else {
-                let from = (m & 0xff) as usize;
-                let to = ((m >> 8) & 0xff) as usize;
+                let from = mv.from().index();
+                let to = mv.to().index();
                 let captured = self.board[to] & PIECE_MASK;
-                if captured != EMPTY {
-                    let piece = self.board[from] & PIECE_MASK;
-                    1000000usize + captured * 10usize - piece
+                let piece = self.board[from] & PIECE_MASK;
+                if !self.is_quiet(mv) {
+                    // captures and promotions, by most valuable victim and
+                    // least valuable attacker
+                    let mvv_lva = (captured + mv.promoted()) * 10 + KING - piece;
+                    if self.see(mv) >= 0 { ORDER_GOOD_CAPTURE + mvv_lva } else { mvv_lva }
+                } else if hints.killers.contains(&mv) {
+                    ORDER_KILLER + if mv == hints.killers[0] { 1 } else { 0 }
+                } else if mv == hints.countermove {
+                    ORDER_COUNTERMOVE
                 } else {
-                    cutoff_moves[REV8X8[from] * 64 + REV8X8[to]]
+                    (ORDER_QUIET + hints.history[from * 64 + to]) as usize
                 }
             };
-            *mv = (score as u64) << 32 | m;
+            scored.push(ScoredMove { mv, score });
         }
+        scored
+    }
+
+    pub fn eval_params(self: &Game) -> EvalParams {
+        self.eval_params
+    }
+
+    pub fn set_eval_params(self: &mut Game, params: EvalParams) {
+        self.eval_params = params;
     }
 
     pub fn evaluate(self: &Game) -> isize {
@@ -1017,13 +1212,11 @@ impl Game {
         let mut white_pawn_rank: [usize; 10] = [0; 10];
         let mut black_pawn_rank: [usize; 10] = [7; 10];
 
-        for i in 0..64 {
-            let pos = MAP8X8[i];
-            let piece = self.board[pos];
+        for (i, &piece) in self.board.iter().enumerate() {
             match piece {
                 WHITE_PAWN => {
                     white_pawn_mat += PAWN_VALUE;
-                    let f = pos % 10;
+                    let f = i % 8 + 1;
                     white_pawn_rank[f] = std::cmp::max(white_pawn_rank[f], i / 8);
                 },
                 WHITE_BISHOP => {
@@ -1040,7 +1233,7 @@ impl Game {
                 },
                 BLACK_PAWN => {
                     black_pawn_mat += PAWN_VALUE;
-                    let f = pos % 10;
+                    let f = i % 8 + 1;
                     black_pawn_rank[f] = std::cmp::min(black_pawn_rank[f], i / 8);
                 },
                 BLACK_BISHOP => {
@@ -1062,11 +1255,10 @@ impl Game {
         let mut white_score: isize = white_piece_mat + white_pawn_mat;
         let mut black_score: isize = black_piece_mat + black_pawn_mat;
 
-        for i in 0..64 {
-            let piece = self.board[MAP8X8[i]];
+        for (i, &piece) in self.board.iter().enumerate() {
             match piece {
                 WHITE_PAWN => {
-                    white_score += evaluate_white_pawn(i, &white_pawn_rank, &black_pawn_rank);
+                    white_score += evaluate_white_pawn(i, &white_pawn_rank, &black_pawn_rank, &self.eval_params);
                 },
                 WHITE_BISHOP => {
                     white_score += BISHOP_PCSQ[i];
@@ -1077,9 +1269,9 @@ impl Game {
                 WHITE_ROOK => {
                     if white_pawn_rank[i % 8 + 1] == 0 {
                         white_score += if black_pawn_rank[i % 8 + 1] == 7 {
-                            ROOK_OPEN_FILE_BONUS
+                            self.eval_params.rook_open_file_bonus
                         } else {
-                            ROOK_SEMI_OPEN_FILE_BONUS
+                            self.eval_params.rook_semi_open_file_bonus
                         }
                     }
                 },
@@ -1092,7 +1284,7 @@ impl Game {
                     }
                 },
                 BLACK_PAWN => {
-                    black_score += evaluate_black_pawn(i, &white_pawn_rank, &black_pawn_rank);
+                    black_score += evaluate_black_pawn(i, &white_pawn_rank, &black_pawn_rank, &self.eval_params);
                 },
                 BLACK_BISHOP => {
                     black_score += BISHOP_PCSQ[FLIP[i]];
@@ -1103,9 +1295,9 @@ impl Game {
                 BLACK_ROOK => {
                     if black_pawn_rank[i % 8 + 1] == 7 {
                         black_score += if white_pawn_rank[i % 8 + 1] == 0 {
-                            ROOK_OPEN_FILE_BONUS
+                            self.eval_params.rook_open_file_bonus
                         } else {
-                            ROOK_SEMI_OPEN_FILE_BONUS
+                            self.eval_params.rook_semi_open_file_bonus
                         }
                     }
                 },
@@ -1131,9 +1323,19 @@ impl Game {
 
     pub fn in_check(self: &Game) -> bool {
         let side = self.state & 0xff;
-        let king_position = if side == WHITE { self.king_white } else { self.king_black };
         let xside = if side == WHITE { BLACK } else { WHITE };
-        self.is_attacked_by(king_position, xside)
+        self.is_attacked_by(self.king_position(side), xside)
+    }
+
+    pub fn side_to_move(self: &Game) -> Color {
+        Color::from_code(self.state & 0xff)
+    }
+
+    pub fn piece_at(self: &Game, square: Square) -> Option<(Color, Piece)> {
+        match self.board[square.index()] {
+            EMPTY => None,
+            piece => Some((Color::from_code(piece & COLOR_MASK), Piece::from_code(piece)))
+        }
     }
 
     pub fn white_to_move(self: &Game) -> bool {
@@ -1159,85 +1361,370 @@ impl Game {
         reps
     }
 
+    // Neither side can mate: bare kings, a single minor piece, or only
+    // bishops all on squares of one color.
+    pub fn insufficient_material(self: &Game) -> bool {
+        if self.pieces[PAWN] | self.pieces[ROOK] | self.pieces[QUEEN] != 0 {
+            return false;
+        }
+        let bishops = self.pieces[BISHOP];
+        (self.pieces[KNIGHT] | bishops).count_ones() <= 1
+            || (self.pieces[KNIGHT] == 0 && (bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0))
+    }
+
+    pub fn status(self: &Game) -> GameStatus {
+        if self.legal_moves().is_empty() {
+            return if self.in_check() {
+                GameStatus::Checkmate(if self.white_to_move() { Color::Black } else { Color::White })
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+        if self.fifty_move_draw() {
+            GameStatus::FiftyMove
+        } else if self.repetitions() >= 2 {
+            GameStatus::ThreefoldRepetition
+        } else if self.insufficient_material() {
+            GameStatus::InsufficientMaterial
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
 }
 
 pub fn millis_since(time: &Instant) -> u64 {
     let elapsed = time.elapsed();
-    return 1000 * elapsed.as_secs() + elapsed.subsec_millis() as u64;
+    1000 * elapsed.as_secs() + elapsed.subsec_millis() as u64
+}
+
+// Shared with a running search. Stopping ends it, and while pondering its
+// time limits wait; the clock starts when pondering ends (ponderhit).
+#[derive(Default)]
+pub struct SearchControl {
+    stop: AtomicBool,
+    ponder: AtomicBool,
+}
+
+impl SearchControl {
+
+    pub fn new(ponder: bool) -> SearchControl {
+        SearchControl { stop: AtomicBool::new(false), ponder: AtomicBool::new(ponder) }
+    }
+
+    pub fn stop(self: &SearchControl) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn ponderhit(self: &SearchControl) {
+        self.ponder.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(self: &SearchControl) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    pub fn is_pondering(self: &SearchControl) -> bool {
+        self.ponder.load(Ordering::Relaxed)
+    }
+}
+
+// A progress report, sent whenever a root move improves on a line.
+pub struct SearchInfo<'a> {
+    pub depth: usize,
+    pub multipv: usize,  // the rank of the line, 1 for the best
+    pub score: isize,
+    pub mate_in: isize,  // non-zero when the score is a mate
+    pub bound: Bound,  // Lower or Upper when the score is outside the root window
+    pub node_count: usize,
+    pub millis: u64,
+    pub pv: &'a [Move],
 }
 
 pub trait ThinkInfo {
-    fn think_info(&mut self, depth: usize, score: isize, mate_in: isize, node_count: usize,
-                  millis: u64, moves: &Vec<String>);
+    fn think_info(&mut self, info: &SearchInfo);
+}
+
+// Moves to mate, negative when being mated, or 0.
+fn mate_in(score: isize) -> isize {
+    if score <= -(MATE_VALUE - MAX_DEPTH as isize) {
+        -(MATE_VALUE + score) / 2
+    } else if score >= (MATE_VALUE - MAX_DEPTH as isize) {
+        (MATE_VALUE - score) / 2
+    } else {
+        0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SearchOptions {
+    pub multipv: usize,  // the number of best lines to search
+    pub threads: usize,
+    pub null_move_verification: bool,  // confirm null-move cutoffs with a reduced search
+    pub late_move_reductions: bool,
+    pub lmr_base: usize,  // reduction = base + ln(depth) * ln(move number) / divisor,
+    pub lmr_divisor: usize,  // both in hundredths of a ply
+    pub late_move_pruning: bool,
+    pub lmp_base: usize,  // quiet moves searched at remaining depth d: base + d * d
+    pub quiescence_checks: bool,  // quiet checks at the first quiescence ply
+    pub delta_pruning: bool,
+    pub delta_margin: usize,  // centipawns a capture must come within of alpha
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            multipv: 1,
+            threads: 1,
+            null_move_verification: false,
+            late_move_reductions: true,
+            lmr_base: 75,
+            lmr_divisor: 225,
+            late_move_pruning: true,
+            lmp_base: 3,
+            quiescence_checks: false,
+            delta_pruning: true,
+            delta_margin: 200,
+        }
+    }
+}
+
+// Late move reductions by remaining depth and move number (from 0).
+fn reduction_table(options: &SearchOptions) -> [[u8; 64]; 64] {
+    let mut table = [[0u8; 64]; 64];
+    if !options.late_move_reductions {
+        return table;
+    }
+    for (depth, row) in table.iter_mut().enumerate().skip(1) {
+        for (number, reduction) in row.iter_mut().enumerate().skip(1) {
+            let r = options.lmr_base as f64 / 100.0
+                    + (depth as f64).ln() * (number as f64).ln() * 100.0 / options.lmr_divisor.max(1) as f64;
+            *reduction = r.clamp(0.0, 255.0) as u8;
+        }
+    }
+    table
+}
+
+// Progress reports of helper threads are not shown.
+struct NoInfo;
+
+impl ThinkInfo for NoInfo {
+    fn think_info(&mut self, _info: &SearchInfo) {}
 }
 
 pub struct Search<'a, T: ThinkInfo> {
     game: &'a mut Game,
+    tt: &'a TranspositionTable,
     comms: &'a mut T,
     nodes: usize,
+    total_nodes: &'a AtomicUsize,  // of all threads, updated every 1024 nodes
     start_time: Instant,
+    clock_start: Instant,  // the start time, or when pondering ended
     max_millis: u64,
+    control: &'a SearchControl,
+    pondering: bool,
     pv: Vec<Vec<Move>>,
     tmp_pv: Vec<Move>,
     stop_thinking: bool,
-    cutoff_moves: [usize; 64 * 64],
+    killers: [[Move; 2]; MAX_DEPTH + 1],
+    history: [[i32; 64 * 64]; 2],  // by side to move
+    countermoves: [Move; 64 * 64],  // by the from * 64 + to of the previous move
+    move_stack: [Move; MAX_DEPTH + 1],  // the moves leading to each ply
+    excluded: Vec<Move>,  // root moves of the better lines in MultiPV
+    multipv_index: usize,
+    options: SearchOptions,
+    reductions: [[u8; 64]; 64],
 }
 
 impl<'a, T: ThinkInfo> Search<'a, T> {
 
-    pub fn new(game: &'a mut Game, max_millis: u64, comms: &'a mut T) -> Search<'a, T> {
+    pub fn new(game: &'a mut Game, tt: &'a TranspositionTable, total_nodes: &'a AtomicUsize, max_millis: u64,
+               control: &'a SearchControl, comms: &'a mut T) -> Search<'a, T> {
         let mut pv: Vec<Vec<Move>> = Vec::with_capacity(MAX_DEPTH + 1);
         for _ in 0..(MAX_DEPTH + 1) {
             pv.push(Vec::with_capacity(MAX_DEPTH + 1));
         }
         Search {
             game,
+            tt,
             comms,
             nodes: 0,
+            total_nodes,
             start_time: Instant::now(),
+            clock_start: Instant::now(),
             max_millis,
+            control,
+            pondering: control.is_pondering(),
             pv,
             tmp_pv: Vec::with_capacity(MAX_DEPTH + 1),
             stop_thinking: false,
-            cutoff_moves: [0; 64 * 64],
+            killers: [[DUMMY_MOVE; 2]; MAX_DEPTH + 1],
+            history: [[0; 64 * 64]; 2],
+            countermoves: [DUMMY_MOVE; 64 * 64],
+            move_stack: [DUMMY_MOVE; MAX_DEPTH + 1],
+            excluded: Vec::new(),
+            multipv_index: 0,
+            options: SearchOptions::default(),
+            reductions: reduction_table(&SearchOptions::default()),
+        }
+    }
+
+    pub fn set_options(self: &mut Search<'a, T>, options: &SearchOptions) {
+        self.options = *options;
+        self.reductions = reduction_table(options);
+    }
+
+    // Halves the history scores, so that each iteration mostly sees its own.
+    fn age_history(self: &mut Search<'a, T>) {
+        for score in self.history.iter_mut().flatten() {
+            *score /= 2;
         }
     }
 
+    // History updates with gravity: the closer a score is to HISTORY_MAX the
+    // less a bonus moves it, which keeps scores in range.
+    fn update_history(self: &mut Search<'a, T>, mv: Move, bonus: i32) {
+        let side = color_index(self.game.state & 0xff);
+        let score = &mut self.history[side][mv.from().index() * 64 + mv.to().index()];
+        *score += bonus - *score * bonus.abs() / HISTORY_MAX;
+    }
+
+    // A quiet move caused a cutoff after the quiet moves in `tried` failed.
+    fn update_quiet_cutoff(self: &mut Search<'a, T>, mv: Move, tried: &[Move], ply: usize, remaining_depth: usize) {
+        if self.killers[ply][0] != mv {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = mv;
+        }
+        if ply > 0 {
+            let previous = self.move_stack[ply - 1];
+            if previous != DUMMY_MOVE {
+                self.countermoves[previous.from().index() * 64 + previous.to().index()] = mv;
+            }
+        }
+        let bonus = std::cmp::min(remaining_depth * remaining_depth, 400) as i32;
+        self.update_history(mv, bonus);
+        for &other in tried {
+            self.update_history(other, -bonus);
+        }
+    }
+
+    fn ordering_hints(self: &Search<'a, T>, ply: usize, pv_move: Move, hash_move: Move) -> OrderingHints<'_> {
+        let previous = if ply > 0 { self.move_stack[ply - 1] } else { DUMMY_MOVE };
+        OrderingHints {
+            pv_move,
+            hash_move,
+            killers: self.killers[ply],
+            countermove: if previous != DUMMY_MOVE {
+                self.countermoves[previous.from().index() * 64 + previous.to().index()]
+            } else {
+                DUMMY_MOVE
+            },
+            history: &self.history[color_index(self.game.state & 0xff)],
+        }
+    }
+
+    fn report(self: &mut Search<'a, T>, depth: usize, score: isize, bound: Bound) {
+        self.comms.think_info(&SearchInfo {
+            depth,
+            multipv: self.multipv_index + 1,
+            score,
+            mate_in: mate_in(score),
+            bound,
+            node_count: self.total_nodes.load(Ordering::Relaxed) + self.nodes % 1024,
+            millis: millis_since(&self.start_time),
+            pv: &self.pv[0],
+        });
+    }
+
+    fn update_clock(self: &mut Search<'a, T>) {
+        if self.pondering && !self.control.is_pondering() {
+            self.pondering = false;
+            self.clock_start = Instant::now();
+        }
+    }
+
+    // Polled every 1024 nodes, both the time limit and the stop flag end
+    // the search.
+    fn check_stop(self: &mut Search<'a, T>) -> bool {
+        if self.nodes.is_multiple_of(1024) {
+            self.total_nodes.fetch_add(1024, Ordering::Relaxed);
+            self.update_clock();
+            if self.control.is_stopped()
+                    || (!self.pondering && millis_since(&self.clock_start) >= self.max_millis) {
+                self.stop_thinking = true;
+            }
+        }
+        self.stop_thinking
+    }
+
+    // Captures from a static evaluation the side to move may stand on, and
+    // with `checks` the quiet checks too. In check there is no standing:
+    // every evasion is searched and having none is mate.
     pub fn quiesce(self: &mut Search<'a, T>, alpha: isize, beta: isize,
-                   ply: usize, follow_pv: bool) -> isize {
+                   ply: usize, follow_pv: bool, checks: bool) -> isize {
         self.nodes += 1;
 
-        if self.nodes % 1024 == 0 && millis_since(&self.start_time) >= self.max_millis {
-            self.stop_thinking = true;
+        if self.check_stop() {
             return 0;  // return value will be ignored
         }
 
-        let mut score = self.game.evaluate();
+        let hash = self.game.hash;
+        let mut hash_move = DUMMY_MOVE;
+        if let Some(entry) = self.tt.probe(hash, ply) {
+            if let Some(score) = entry.cutoff(alpha, beta) {
+                return score;
+            }
+            hash_move = entry.mv;
+        }
+
+        let eval = self.game.evaluate();
+        let in_check = self.game.in_check();
+        let original_alpha = alpha;
         let mut alpha = alpha;
 
         if ply == MAX_DEPTH - 1 {
-            return score;
+            return eval;
         }
-        if score >= beta {
-            return beta;
-        }
-        if score > alpha {
-            alpha = score;
+        if !in_check {
+            if eval >= beta {
+                return beta;
+            }
+            if eval > alpha {
+                alpha = eval;
+            }
         }
 
-        let mut moves = self.game.capture_moves();
         let mut follow_pv = follow_pv && ply < self.pv[0].len();
-        self.game.score_moves(&mut moves, &self.cutoff_moves, if follow_pv { self.pv[0][ply] } else { DUMMY_MOVE });
-
-        moves.sort_unstable_by(|a, b| (*b >> 32).cmp(&(*a >> 32)));
+        let mut best_move = DUMMY_MOVE;
+        let pv_move = if follow_pv { self.pv[0][ply] } else { DUMMY_MOVE };
+        // captures that lose material by static exchange evaluation are
+        // unlikely to raise alpha, and the picker leaves them out
+        let mut picker = if in_check {
+            let hints = self.ordering_hints(ply, pv_move, hash_move);
+            MovePicker::new(pv_move, hash_move, hints.killers, hints.countermove)
+        } else if checks {
+            MovePicker::captures_and_checks(pv_move, hash_move)
+        } else {
+            MovePicker::captures(pv_move, hash_move)
+        };
+        let side = color_index(self.game.state & 0xff);
+        let mut any_legal_moves = false;
 
-        for mv in moves {
+        while let Some(mv) = picker.next(self.game, &self.history[side]) {
+            // delta pruning: skip captures that leave the score short of
+            // alpha even when the captured piece comes for free
+            if self.options.delta_pruning && !in_check && !self.game.is_quiet(mv)
+                    && eval + self.game.material_gain(mv) + (self.options.delta_margin as isize) <= alpha {
+                continue;
+            }
             if !self.game.make_move(mv) {
                 continue;
             }
+            self.move_stack[ply] = mv;
+            any_legal_moves = true;
 
-            self.pv[ply + 1].clear();    
-            score = -self.quiesce(-beta, -alpha, ply + 1, follow_pv);
+            self.pv[ply + 1].clear();
+            let score = -self.quiesce(-beta, -alpha, ply + 1, follow_pv, false);
 
             self.game.unmake_move(mv);
 
@@ -1246,10 +1733,12 @@ impl<'a, T: ThinkInfo> Search<'a, T> {
             }
             if score > alpha {
                 if score >= beta {
+                    self.tt.store(hash, ply, 0, beta, Bound::Lower, mv);
                     return beta;
                 }
                 alpha = score;
-                
+                best_move = mv;
+
                 self.tmp_pv.push(mv);
                 self.tmp_pv.append(&mut self.pv[ply + 1]);
                 self.pv[ply].clear();
@@ -1258,19 +1747,25 @@ impl<'a, T: ThinkInfo> Search<'a, T> {
             follow_pv = false;
         }
 
+        if in_check && !any_legal_moves {
+            return -MATE_VALUE + ply as isize;
+        }
+
+        self.tt.store(hash, ply, 0, alpha,
+                      if alpha > original_alpha { Bound::Exact } else { Bound::Upper }, best_move);
+
         alpha
     }
 
     pub fn search(self: &mut Search<'a, T>, alpha: isize, beta: isize,
-                  ply: usize, depth: usize, follow_pv: bool) -> isize {
+                  ply: usize, depth: usize, follow_pv: bool, allow_null: bool) -> isize {
         if ply >= depth {
-            return self.quiesce(alpha, beta, ply, follow_pv);
+            return self.quiesce(alpha, beta, ply, follow_pv, self.options.quiescence_checks);
         }
 
         self.nodes += 1;
 
-        if self.nodes % 1024 == 0 && millis_since(&self.start_time) >= self.max_millis {
-            self.stop_thinking = true;
+        if self.check_stop() {
             return 0;  // return value will be ignored
         }
 
@@ -1284,30 +1779,111 @@ impl<'a, T: ThinkInfo> Search<'a, T> {
             return self.game.evaluate();
         }
 
-        let mut moves = self.game.generate_moves();
+        let hash = self.game.hash;
+        let remaining_depth = depth - ply;
+        let mut hash_move = DUMMY_MOVE;
+        if let Some(entry) = self.tt.probe(hash, ply) {
+            if ply > 0 && entry.depth >= remaining_depth {
+                if let Some(score) = entry.cutoff(alpha, beta) {
+                    return score;
+                }
+            }
+            hash_move = entry.mv;
+        }
+
+        let in_check = self.game.in_check();
+
+        // null-move pruning: if passing the turn still fails high, some move
+        // will too. Not in check, not twice in a row and not without pieces,
+        // where zugzwang makes passing look too good.
+        if allow_null && ply > 0 && !follow_pv && !in_check && remaining_depth >= 2
+                && beta < MATE_VALUE - MAX_DEPTH as isize
+                && self.game.has_non_pawn_material() && self.game.evaluate() >= beta {
+            let reduced_depth = depth - if remaining_depth > 6 { 3 } else { 2 };
+            self.game.make_null_move();
+            self.move_stack[ply] = DUMMY_MOVE;
+            self.pv[ply + 1].clear();
+            let score = -self.search(-beta, -beta + 1, ply + 1, reduced_depth, false, false);
+            self.game.unmake_null_move();
+            if self.stop_thinking {
+                return 0;  // return value will be ignored
+            }
+            // the verification search is a reduced search without a null move
+            if score >= beta && (!self.options.null_move_verification
+                                 || self.search(beta - 1, beta, ply, reduced_depth, false, false) >= beta) {
+                return beta;
+            }
+            if self.stop_thinking {
+                return 0;  // return value will be ignored
+            }
+        }
+
         let mut any_legal_moves = false;
+        let original_alpha = alpha;
         let mut alpha = alpha;
+        let mut best_move = DUMMY_MOVE;
         let mut depth = depth;
-        let in_check = self.game.in_check();
 
         if in_check {
             depth += 1;
         }
 
         let mut follow_pv = follow_pv && ply < self.pv[0].len();
-        self.game.score_moves(&mut moves, &self.cutoff_moves, if follow_pv { self.pv[0][ply] } else { DUMMY_MOVE });
-
-        moves.sort_unstable_by(|a, b| (*b >> 32).cmp(&(*a >> 32)));
-
-        for mv in moves {
-
+        let hints = self.ordering_hints(ply, if follow_pv { self.pv[0][ply] } else { DUMMY_MOVE }, hash_move);
+        let (killers, countermove) = (hints.killers, hints.countermove);
+        let mut picker = MovePicker::new(hints.pv_move, hints.hash_move, killers, countermove);
+        let side = color_index(self.game.state & 0xff);
+        let mut quiets_tried: Vec<Move> = Vec::new();
+
+        // late move pruning: near the leaves of a zero-window search, when
+        // the position is no better than alpha, the late quiet moves are
+        // skipped altogether
+        let prune_late_moves = self.options.late_move_pruning && !in_check && beta - alpha == 1
+                               && remaining_depth <= 3 && self.game.evaluate() <= alpha;
+        let mut move_count = 0;
+        while let Some(mv) = picker.next(self.game, &self.history[side]) {
+
+            if ply == 0 && self.excluded.contains(&mv) {
+                continue;
+            }
+            let quiet = self.game.is_quiet(mv);
+            if prune_late_moves && quiet && move_count >= self.options.lmp_base + remaining_depth * remaining_depth {
+                continue;
+            }
             if !self.game.make_move(mv) {
                 continue;
             }
+            self.move_stack[ply] = mv;
+            let first_move = !any_legal_moves;
             any_legal_moves = true;
-
-            self.pv[ply + 1].clear();    
-            let score = -self.search(-beta, -alpha, ply + 1, depth, follow_pv);
+            move_count += 1;
+
+            // principal variation search: the first move is searched with the
+            // full window, the others only to show they are no better, late
+            // quiet moves first with reduced depth (late move reductions)
+            self.pv[ply + 1].clear();
+            let mut score = if first_move {
+                -self.search(-beta, -alpha, ply + 1, depth, follow_pv, true)
+            } else {
+                let reduction = if quiet && !in_check && remaining_depth >= 3 && move_count > 3
+                                   && !killers.contains(&mv) && mv != countermove && !self.game.in_check() {
+                    let r = self.reductions[remaining_depth.min(63)][(move_count - 1).min(63)] as usize;
+                    // less in the principal variation
+                    let r = if beta - alpha > 1 { r.saturating_sub(1) } else { r };
+                    r.min(remaining_depth - 2)
+                } else {
+                    0
+                };
+                let mut score = -self.search(-alpha - 1, -alpha, ply + 1, depth - reduction, false, true);
+                if reduction > 0 && score > alpha && !self.stop_thinking {
+                    score = -self.search(-alpha - 1, -alpha, ply + 1, depth, false, true);
+                }
+                score
+            };
+            if !first_move && score > alpha && score < beta && !self.stop_thinking {
+                self.pv[ply + 1].clear();
+                score = -self.search(-beta, -alpha, ply + 1, depth, false, true);
+            }
 
             self.game.unmake_move(mv);
 
@@ -1315,12 +1891,22 @@ impl<'a, T: ThinkInfo> Search<'a, T> {
                 return 0;  // return value will be ignored
             }
             if score >= beta {
+                self.tt.store(hash, ply, remaining_depth, beta, Bound::Lower, mv);
+                if quiet {
+                    self.update_quiet_cutoff(mv, &quiets_tried, ply, remaining_depth);
+                }
+                if ply == 0 {
+                    // the root window failed high, the move is reported by think
+                    self.tmp_pv.push(mv);
+                    self.tmp_pv.append(&mut self.pv[1]);
+                    self.pv[0].clear();
+                    self.pv[0].append(&mut self.tmp_pv);
+                }
                 return beta;
             }
             if score > alpha {
                 alpha = score;
-
-                self.cutoff_moves[REV8X8[mv.from()] * 64 + REV8X8[mv.to()]] += MAX_DEPTH - ply;
+                best_move = mv;
 
                 self.tmp_pv.push(mv);
                 self.tmp_pv.append(&mut self.pv[ply + 1]);
@@ -1328,18 +1914,12 @@ impl<'a, T: ThinkInfo> Search<'a, T> {
                 self.pv[ply].append(&mut self.tmp_pv);
 
                 if ply == 0 {
-                    let mate_in: isize = if score <= -(MATE_VALUE - MAX_DEPTH as isize) {
-                        -(MATE_VALUE + score) / 2
-                    } else if score >= (MATE_VALUE - MAX_DEPTH as isize) {
-                        (MATE_VALUE - score) / 2
-                    } else {
-                        0
-                    };
-                    let millis = millis_since(&self.start_time);
-                    let moves = self.pv[0].iter().map(|m| m.to_algebraic()).collect::<Vec<String>>();
-                    self.comms.think_info(depth, score, mate_in, self.nodes, millis, &moves);
+                    self.report(depth, score, Bound::Exact);
                 }
             }
+            if quiet {
+                quiets_tried.push(mv);
+            }
             follow_pv = false;
         }
 
@@ -1351,52 +1931,165 @@ impl<'a, T: ThinkInfo> Search<'a, T> {
             return 0;
         }
 
+        // with root moves excluded the root score is not that of the position
+        if ply > 0 || self.excluded.is_empty() {
+            self.tt.store(hash, ply, remaining_depth, alpha,
+                          if alpha > original_alpha { Bound::Exact } else { Bound::Upper }, best_move);
+        }
+
         alpha
     }
 }
 
-pub fn think<T: ThinkInfo>(game: &mut Game, millis_to_think: u64, search_depth: usize, comms: &mut T) -> Option<Move> {
-    let mut search = Search::new(game, millis_to_think, comms);
+pub struct PvLine {
+    pub depth: usize,
+    pub score: isize,
+    pub moves: Vec<Move>,
+}
 
-    for depth in 1..(search_depth + 1) {
-        let score = search.search(-MATE_VALUE, MATE_VALUE, 0, depth, true);
-        if search.stop_thinking {
-            break;
-        }
-        if score >= MATE_VALUE - MAX_DEPTH as isize {
-            break;
-        }
+pub struct ThinkResult {
+    pub best_move: Move,
+    pub lines: Vec<PvLine>,  // best first, more than one in MultiPV
+}
+
+impl ThinkResult {
+    pub fn pv(self: &ThinkResult) -> &[Move] {
+        &self.lines[0].moves
     }
 
-    if search.pv[0].len() > 0 {
-        Some(search.pv[0][0])
-    } else {
-        None
+    // The expected reply, to ponder on
+    pub fn ponder_move(self: &ThinkResult) -> Option<Move> {
+        self.pv().get(1).copied()
     }
 }
 
-fn legal_moves(game: &mut Game) -> Vec<Move> {
-    let mut result: Vec<Move> = Vec::new();
-    let move_list = game.generate_moves();
-    for mv in move_list {
-        if game.make_move(mv) {
-            game.unmake_move(mv);
-            result.push(mv);
-        }
+// Half the width of the first root window, in centipawns.
+const ASPIRATION_WINDOW: isize = 25;
+
+// Searches until the time is up, the depth is reached or the search is
+// stopped. Unless there are no legal moves a move is returned, even if
+// stopped early. In MultiPV each further line is searched without the first
+// moves of the better lines.
+//
+// With more than one thread (lazy SMP) helper threads search copies of the
+// game through the shared transposition table, which lets the main thread
+// skip much of its work. Only the main thread reports and decides the move.
+pub fn think<T: ThinkInfo>(game: &mut Game, tt: &TranspositionTable, limits: &TimeLimits, search_depth: usize,
+                           options: &SearchOptions, control: &SearchControl, comms: &mut T) -> Option<ThinkResult> {
+    // checkmate or stalemate, nothing to search
+    let root_moves = game.legal_moves();
+    if root_moves.is_empty() {
+        return None;
     }
-    result
-}
+    let total_nodes = AtomicUsize::new(0);
+    let helper_control = SearchControl::new(false);
+    let helper_games: Vec<Game> = (1..options.threads).map(|_| game.clone()).collect();
+    std::thread::scope(|scope| {
+        for (i, mut helper_game) in helper_games.into_iter().enumerate() {
+            let (total_nodes, helper_control) = (&total_nodes, &helper_control);
+            scope.spawn(move || {
+                let mut info = NoInfo;
+                let mut search = Search::new(&mut helper_game, tt, total_nodes, u64::MAX, helper_control, &mut info);
+                search.set_options(options);
+                // every other helper is a depth ahead, to spread the work
+                for depth in (1 + (i + 1) % 2)..(search_depth + 1) {
+                    search.age_history();
+                    search.search(-MATE_VALUE, MATE_VALUE, 0, depth, true, true);
+                    if search.stop_thinking {
+                        break;
+                    }
+                }
+            });
+        }
 
-pub fn make_move_algebraic(game: &mut Game, input_move: &str) {
-    let input_move = algebraic_to_move(input_move);
-    let moves = legal_moves(game);
-    for mv in moves {
-        if mv == input_move {
-            game.make_move(mv);
-            return;
+        let line_count = options.multipv.clamp(1, root_moves.len());
+        let mut search = Search::new(game, tt, &total_nodes, limits.hard, control, comms);
+        search.set_options(options);
+        let mut lines: Vec<PvLine> = Vec::with_capacity(line_count);
+        let mut last_iteration: u64 = 0;
+
+        'iterations: for depth in 1..(search_depth + 1) {
+            let iteration_start = millis_since(&search.start_time);
+            search.age_history();
+            search.excluded.clear();
+            for k in 0..line_count {
+                search.multipv_index = k;
+                search.pv[0] = lines.get(k).map_or(Vec::new(), |line| line.moves.clone());
+                // aspiration window around the last score of the line, widened
+                // on the failing side until the score falls inside
+                let mut delta = ASPIRATION_WINDOW;
+                let (mut alpha, mut beta) = match lines.get(k) {
+                    Some(line) if depth > 4 => (std::cmp::max(line.score - delta, -MATE_VALUE),
+                                                std::cmp::min(line.score + delta, MATE_VALUE)),
+                    _ => (-MATE_VALUE, MATE_VALUE),
+                };
+                let score = loop {
+                    let score = search.search(alpha, beta, 0, depth, true, true);
+                    if search.stop_thinking {
+                        break score;
+                    }
+                    delta *= 2;
+                    if score <= alpha && alpha > -MATE_VALUE {
+                        search.report(depth, alpha, Bound::Upper);
+                        alpha = std::cmp::max(alpha - delta, -MATE_VALUE);
+                    } else if score >= beta && beta < MATE_VALUE {
+                        search.report(depth, beta, Bound::Lower);
+                        beta = std::cmp::min(beta + delta, MATE_VALUE);
+                    } else {
+                        break score;
+                    }
+                };
+                if search.stop_thinking {
+                    // the lines of the last completed iteration stand, as the
+                    // score of an unfinished one is not known; only without
+                    // them is its best move so far kept
+                    if lines.is_empty() && !search.pv[0].is_empty() {
+                        lines.push(PvLine { depth: 0, score: 0, moves: search.pv[0].clone() });
+                    }
+                    break 'iterations;
+                }
+                // a line is only kept with its moves
+                let line = match search.pv[0].first() {
+                    Some(&mv) => {
+                        search.excluded.push(mv);
+                        PvLine { depth, score, moves: search.pv[0].clone() }
+                    },
+                    None => break 'iterations,
+                };
+                if k < lines.len() {
+                    lines[k] = line;
+                } else {
+                    lines.push(line);
+                }
+            }
+            if lines[0].score >= MATE_VALUE - MAX_DEPTH as isize {
+                break;
+            }
+            // predict the next iteration from the growth of the last two
+            let iteration = millis_since(&search.start_time) - iteration_start;
+            let growth = iteration.checked_div(last_iteration).map_or(2, |g| g.clamp(2, 8));
+            search.update_clock();
+            if !search.pondering && !limits.allows_iteration(millis_since(&search.clock_start), iteration * growth) {
+                break;
+            }
+            last_iteration = iteration;
+        }
+
+        if lines.is_empty() {
+            lines.extend(root_moves.first().map(|&mv| PvLine { depth: 0, score: 0, moves: vec![mv] }));
         }
+        helper_control.stop();
+        lines.first().map(|line| line.moves[0]).map(|best_move| ThinkResult { best_move, lines })
+    })
+}
+
+pub fn make_move_algebraic(game: &mut Game, input_move: &str) -> Result<(), SanError> {
+    let input_move = algebraic_to_move(input_move)?;
+    if !game.legal_moves().contains(&input_move) {
+        return Err(SanError::Illegal);
     }
-    panic!("make_move_algebraic");
+    game.make_move(input_move);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1406,20 +2099,382 @@ mod tests {
     #[test]
     fn three_fold_repetition() {
         let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0").unwrap();
-        make_move_algebraic(&mut game, "g1f3");
-        make_move_algebraic(&mut game, "b8c6");
-        make_move_algebraic(&mut game, "f3g1");
-        make_move_algebraic(&mut game, "c6b8");
+        make_move_algebraic(&mut game, "g1f3").unwrap();
+        make_move_algebraic(&mut game, "b8c6").unwrap();
+        make_move_algebraic(&mut game, "f3g1").unwrap();
+        make_move_algebraic(&mut game, "c6b8").unwrap();
         assert_eq!(game.repetitions(), 1);
-        make_move_algebraic(&mut game, "g1f3");
-        make_move_algebraic(&mut game, "b8c6");
-        make_move_algebraic(&mut game, "f3g1");
-        make_move_algebraic(&mut game, "c6b8");
+        make_move_algebraic(&mut game, "g1f3").unwrap();
+        make_move_algebraic(&mut game, "b8c6").unwrap();
+        make_move_algebraic(&mut game, "f3g1").unwrap();
+        make_move_algebraic(&mut game, "c6b8").unwrap();
         assert_eq!(game.repetitions(), 2);
-        make_move_algebraic(&mut game, "g1f3");
-        make_move_algebraic(&mut game, "b8c6");
-        make_move_algebraic(&mut game, "f3g1");
-        make_move_algebraic(&mut game, "c6b8");
+        make_move_algebraic(&mut game, "g1f3").unwrap();
+        make_move_algebraic(&mut game, "b8c6").unwrap();
+        make_move_algebraic(&mut game, "f3g1").unwrap();
+        make_move_algebraic(&mut game, "c6b8").unwrap();
         assert_eq!(game.repetitions(), 3);
     }
+
+    struct MateInfo {
+        score: isize,
+    }
+
+    impl ThinkInfo for MateInfo {
+        fn think_info(&mut self, info: &SearchInfo) {
+            if info.bound == Bound::Exact {
+                self.score = info.score;
+            }
+        }
+    }
+
+    #[test]
+    fn squares_and_moves() {
+        let e4 = Square::from_algebraic("e4").unwrap();
+        assert_eq!((e4.file(), e4.rank()), (4, 3));
+        assert_eq!(e4, Square::new(4, 3));
+        assert_eq!(e4.to_string(), "e4");
+        assert_eq!(Square::from_algebraic("i1"), None);
+        assert_eq!(Square::from_algebraic("a9"), None);
+        assert_eq!(Square::from_algebraic("a"), None);
+
+        let (e7, e8) = (Square::from_algebraic("e7").unwrap(), Square::from_algebraic("e8").unwrap());
+        let mv = Move::new(e7, e8, Some(Piece::Queen)).unwrap();
+        assert_eq!(mv.to_string(), "e7e8q");
+        assert_eq!(mv, algebraic_to_move("e7e8q").unwrap());
+        assert_eq!(mv.promotion(), Some(Piece::Queen));
+        assert_eq!(Move::new(e7, e8, Some(Piece::King)), None);
+        assert_eq!(Move::new(e7, e8, Some(Piece::Pawn)), None);
+        assert_eq!(Move::new(e7, e8, None).unwrap().to_string(), "e7e8");
+        assert_eq!(algebraic_to_move("e7e8k"), Err(SanError::Invalid));
+        assert_eq!(algebraic_to_move("e7"), Err(SanError::Invalid));
+        assert_eq!(algebraic_to_move("e7\u{e9}8"), Err(SanError::Invalid));
+
+        let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0").unwrap();
+        assert_eq!(make_move_algebraic(&mut game, "e2e5"), Err(SanError::Illegal));
+        assert_eq!(make_move_algebraic(&mut game, "e2e"), Err(SanError::Invalid));
+        assert_eq!(game.to_fen(), START_FEN);
+        assert_eq!(game.piece_at(Square::new(4, 0)), Some((Color::White, Piece::King)));
+        assert_eq!(game.piece_at(Square::new(3, 7)), Some((Color::Black, Piece::Queen)));
+        assert_eq!(game.piece_at(e4), None);
+        assert_eq!(game.side_to_move(), Color::White);
+    }
+
+    #[test]
+    fn legal_moves_match_filtered_pseudo_legal_moves() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0",
+            // en passant capture exposing the king along the rank
+            "8/8/8/KPp4r/8/8/8/7k w - c6 0",
+            // en passant capture removing the checking pawn
+            "8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0",
+            // double check
+            "4k3/8/8/8/8/5n2/8/r3K3 w - - 0",
+        ];
+        for fen in fens.iter() {
+            let mut game = Game::from_fen(fen).unwrap();
+            let mut expected: Vec<String> = Vec::new();
+            for mv in game.generate_moves() {
+                if game.make_move(mv) {
+                    game.unmake_move(mv);
+                    expected.push(mv.to_string());
+                }
+            }
+            let mut actual: Vec<String> = game.legal_moves().iter().map(|m| m.to_string()).collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn game_status() {
+        let status = |fen: &str| Game::from_fen(fen).unwrap().status();
+        assert_eq!(status("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0"), GameStatus::Ongoing);
+        assert_eq!(status("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1"),
+                   GameStatus::Checkmate(Color::Black));
+        assert_eq!(status("7k/5Q2/6K1/8/8/8/8/8 b - - 0"), GameStatus::Stalemate);
+        assert_eq!(status("7k/8/6K1/8/8/8/8/R7 w - - 99"), GameStatus::Ongoing);
+        assert_eq!(status("7k/8/6K1/8/8/8/8/R7 b - - 99"), GameStatus::Ongoing);
+        assert_eq!(status("7k/8/8/8/8/8/8/K7 w - - 0"), GameStatus::InsufficientMaterial);
+        assert_eq!(status("7k/8/8/8/8/8/8/KN6 w - - 0"), GameStatus::InsufficientMaterial);
+        assert_eq!(status("5b1k/8/8/8/8/8/8/K1B5 w - - 0"), GameStatus::InsufficientMaterial);
+        assert_eq!(status("6bk/8/8/8/8/8/8/K1B5 w - - 0"), GameStatus::Ongoing);
+        assert_eq!(status("7k/8/8/8/8/8/8/KNN5 w - - 0"), GameStatus::Ongoing);
+
+        let mut game = Game::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 98").unwrap();
+        make_move_algebraic(&mut game, "a1a2").unwrap();
+        assert_eq!(game.status(), GameStatus::Ongoing);
+        make_move_algebraic(&mut game, "h8g8").unwrap();
+        assert_eq!(game.status(), GameStatus::FiftyMove);
+
+        let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0").unwrap();
+        for _ in 0..2 {
+            for mv in ["g1f3", "b8c6", "f3g1", "c6b8"].iter() {
+                assert_eq!(game.status(), GameStatus::Ongoing);
+                make_move_algebraic(&mut game, mv).unwrap();
+            }
+        }
+        assert_eq!(game.status(), GameStatus::ThreefoldRepetition);
+    }
+
+    #[test]
+    fn hash_is_independent_of_game_instance() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0";
+        assert_eq!(Game::from_fen(fen).unwrap().hash(), Game::from_fen(fen).unwrap().hash());
+
+        // e3 is not recorded as en passant square when no black pawn can capture
+        let mut game = Game::from_fen(fen).unwrap();
+        make_move_algebraic(&mut game, "e2e4").unwrap();
+        let other = Game::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0").unwrap();
+        assert_eq!(game.hash(), other.hash());
+        let with_ep = Game::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0").unwrap();
+        assert_eq!(game.hash(), with_ep.hash());
+
+        make_move_algebraic(&mut game, "d7d5").unwrap();
+        make_move_algebraic(&mut game, "e4e5").unwrap();
+        make_move_algebraic(&mut game, "f7f5").unwrap();
+        let no_ep = Game::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0").unwrap();
+        assert_ne!(game.hash(), no_ep.hash());
+    }
+
+    #[test]
+    fn en_passant_hashes() {
+        // reference values computed outside the engine from the same keys
+        assert_eq!(Game::from_fen(START_FEN).unwrap().hash(), 0x61c9f17661d0e4b4);
+        for &(before, mv, fen, hash) in [
+            ("4k3/8/8/8/1p6/8/P7/4K3 w - - 0 1", "a2a4", "4k3/8/8/8/Pp6/8/8/4K3 b - a3 0 1", 0xa3bb2af26a05e3d7),
+            ("4k3/4p3/8/3P4/8/8/8/4K3 b - - 0 1", "e7e5", "4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1", 0x753a40994bc516a0),
+            ("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", "e2e4", "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1", 0x5000625e75437e98),
+        ].iter() {
+            assert_eq!(Game::from_fen(fen).unwrap().hash(), hash, "{}", fen);
+            let mut game = Game::from_fen(before).unwrap();
+            make_move_algebraic(&mut game, mv).unwrap();
+            assert_eq!(game.hash(), hash, "{} {}", before, mv);
+        }
+        assert_eq!(Game::from_fen("4k3/8/8/8/Pp6/8/8/4K3 b - - 0 1").unwrap().hash(), 0x4212c48f3fdb866d);
+        assert_eq!(Game::from_fen("4k3/8/8/3Pp3/8/8/8/4K3 w - - 0 1").unwrap().hash(), 0xaebd1115b08910c1);
+    }
+
+    #[test]
+    fn null_move_passes_the_turn() {
+        let mut game = Game::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+        let hash = game.hash();
+        game.make_null_move();
+        // the en passant capture is gone
+        let passed = Game::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3").unwrap();
+        assert_eq!(game.hash(), passed.hash());
+        assert_eq!(game.to_fen(), "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3");
+        game.unmake_null_move();
+        assert_eq!(game.hash(), hash);
+        assert_eq!(game.to_fen(), "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+    }
+
+    #[test]
+    fn orders_moves_by_kind() {
+        // the queen can take a defended pawn (bad) or the queen (good)
+        let game = Game::from_fen("4k3/2p5/1p3q2/8/8/8/1Q6/4K3 w - - 0 1").unwrap();
+        let mv = |s: &str| algebraic_to_move(s).unwrap();
+        let mut history = [0; 64 * 64];
+        history[mv("b2a1").from().index() * 64 + mv("b2a1").to().index()] = 500;
+        let hints = OrderingHints {
+            pv_move: DUMMY_MOVE,
+            hash_move: mv("e1d1"),
+            killers: [mv("b2c3"), mv("b2d4")],
+            countermove: mv("e1e2"),
+            history: &history,
+        };
+        let mut moves = game.score_moves(&game.legal_moves(), &hints);
+        moves.sort_unstable_by_key(|m| std::cmp::Reverse(m.score));
+        let order: Vec<String> = moves.iter().map(|m| m.mv.to_algebraic()).collect();
+        assert_eq!(&order[..6], ["e1d1", "b2f6", "b2c3", "b2d4", "e1e2", "b2a1"]);
+        assert_eq!(order.last().unwrap(), "b2b6");
+    }
+
+    #[test]
+    fn static_exchange_evaluation() {
+        let see = |fen: &str, mv: &str| Game::from_fen(fen).unwrap().see(algebraic_to_move(mv).unwrap());
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), PAWN_VALUE);
+        // the rook and queen behind each other both take part
+        assert_eq!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"),
+                   PAWN_VALUE - KNIGHT_VALUE);
+        assert_eq!(see("4k3/8/3r4/8/8/8/3R4/3RK3 w - - 0 1", "d2d6"), ROOK_VALUE);
+        assert_eq!(see("4k3/2p5/1p3q2/8/8/8/1Q6/4K3 w - - 0 1", "b2b6"), PAWN_VALUE - QUEEN_VALUE);
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), PAWN_VALUE);
+        // the king recaptures, but not a defended piece
+        assert_eq!(see("3rk3/8/8/8/8/8/3P4/4K3 b - - 0 1", "d8d2"), PAWN_VALUE - ROOK_VALUE);
+        assert_eq!(see("3rk3/3r4/8/8/8/8/3P4/4K3 b - - 0 1", "d7d2"), PAWN_VALUE);
+    }
+
+    #[test]
+    fn mate_in_two_through_transposition_table() {
+        let mut game = Game::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let mut info = MateInfo { score: 0 };
+        let mv = think(&mut game, &tt, &TimeLimits::movetime(60000), 6, &SearchOptions::default(), &SearchControl::new(false), &mut info).unwrap().best_move;
+        assert_eq!(mv.to_algebraic(), "a1a6");
+        assert_eq!(info.score, MATE_VALUE - 3);
+        // a second search sees the stored mate scores from the root's point of view
+        let mv = think(&mut game, &tt, &TimeLimits::movetime(60000), 6, &SearchOptions::default(), &SearchControl::new(false), &mut info).unwrap().best_move;
+        assert_eq!(mv.to_algebraic(), "a1a6");
+        assert_eq!(info.score, MATE_VALUE - 3);
+    }
+
+    #[test]
+    fn helper_threads_share_the_search() {
+        let mut game = Game::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let mut info = MateInfo { score: 0 };
+        let options = SearchOptions { threads: 4, ..SearchOptions::default() };
+        let result = think(&mut game, &tt, &TimeLimits::movetime(60000), 6, &options, &SearchControl::new(false), &mut info).unwrap();
+        assert_eq!(result.best_move.to_algebraic(), "a1a6");
+        assert_eq!(info.score, MATE_VALUE - 3);
+        assert_eq!(game.to_fen(), "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1");
+    }
+
+    #[test]
+    fn gives_check_without_making_the_move() {
+        for fen in ["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                    "8/8/8/K2pP2q/8/8/8/7k w - d6 0 1",
+                    "3k4/8/8/8/8/8/8/R3K2R w KQ - 0 1",
+                    "4k3/1P6/8/8/8/2B5/3P4/4K3 w - - 0 1"] {
+            let mut game = Game::from_fen(fen).unwrap();
+            for mv in game.generate_moves() {
+                let gives_check = game.gives_check(mv);
+                if game.make_move(mv) {
+                    assert_eq!(gives_check, game.in_check(), "{} {}", fen, mv.to_algebraic());
+                    game.unmake_move(mv);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn quiescence_sees_mate() {
+        let tt = TranspositionTable::new(1);
+        let total_nodes = AtomicUsize::new(0);
+        let control = SearchControl::new(false);
+        let mut info = MateInfo { score: 0 };
+        // in check there is no standing pat
+        let mut game = Game::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        let mut search = Search::new(&mut game, &tt, &total_nodes, u64::MAX, &control, &mut info);
+        assert_eq!(search.quiesce(-MATE_VALUE, MATE_VALUE, 0, false, false), -MATE_VALUE);
+
+        let mut game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut search = Search::new(&mut game, &tt, &total_nodes, u64::MAX, &control, &mut info);
+        assert!(search.quiesce(-MATE_VALUE, MATE_VALUE, 0, false, false) < 1000);
+        // with quiet checks, Ra8 is found (the table would give the score above)
+        let tt = TranspositionTable::new(1);
+        let mut search = Search::new(&mut game, &tt, &total_nodes, u64::MAX, &control, &mut info);
+        assert_eq!(search.quiesce(-MATE_VALUE, MATE_VALUE, 0, false, true), MATE_VALUE - 1);
+    }
+
+    #[test]
+    fn reductions_grow_with_depth_and_move_number() {
+        let table = reduction_table(&SearchOptions::default());
+        assert_eq!(table[3][1], 0);
+        assert_eq!(table[3][3], 1);
+        assert!(table[20][40] > table[6][40] && table[20][40] > table[20][6]);
+        let options = SearchOptions { late_move_reductions: false, ..SearchOptions::default() };
+        assert!(reduction_table(&options).iter().flatten().all(|&r| r == 0));
+    }
+
+    #[test]
+    fn no_move_without_legal_moves() {
+        let tt = TranspositionTable::new(1);
+        let mut info = MateInfo { score: 0 };
+        let control = SearchControl::new(false);
+        for fen in ["rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"].iter() {
+            let mut game = Game::from_fen(fen).unwrap();
+            assert!(game.legal_moves().is_empty());
+            assert!(think(&mut game, &tt, &TimeLimits::INFINITE, MAX_DEPTH, &SearchOptions::default(), &control, &mut info).is_none());
+        }
+    }
+
+    #[test]
+    fn stopped_search_returns_a_legal_move() {
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let tt = TranspositionTable::new(1);
+        let mut info = MateInfo { score: 0 };
+        let control = SearchControl::new(false);
+        control.stop();
+        let mv = think(&mut game, &tt, &TimeLimits::INFINITE, MAX_DEPTH, &SearchOptions::default(), &control, &mut info).unwrap().best_move;
+        assert!(game.legal_moves().contains(&mv));
+    }
+
+    // Stops the search at the first new best move of the given depth.
+    struct StopAtDepth<'a> {
+        depth: usize,
+        control: &'a SearchControl,
+        last: Option<(usize, isize, Vec<Move>)>,  // the last best move before
+    }
+
+    impl ThinkInfo for StopAtDepth<'_> {
+        fn think_info(&mut self, info: &SearchInfo) {
+            if info.bound != Bound::Exact {
+                return;
+            }
+            if info.depth >= self.depth {
+                self.control.stop();
+            } else {
+                self.last = Some((info.depth, info.score, info.pv.to_vec()));
+            }
+        }
+    }
+
+    #[test]
+    fn stopped_iteration_keeps_last_line() {
+        let mut game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let control = SearchControl::new(false);
+        let mut info = StopAtDepth { depth: 5, control: &control, last: None };
+        let result = think(&mut game, &tt, &TimeLimits::INFINITE, MAX_DEPTH, &SearchOptions::default(),
+                           &control, &mut info).unwrap();
+        let line = &result.lines[0];
+        assert_eq!(Some((line.depth, line.score, line.moves.clone())), info.last);
+        assert_eq!(line.depth, 4);
+    }
+
+    #[test]
+    fn pondering_waits_for_ponderhit() {
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let tt = TranspositionTable::new(1);
+        let control = SearchControl::new(true);
+        std::thread::scope(|scope| {
+            let search = scope.spawn(|| {
+                let mut info = MateInfo { score: 0 };
+                think(&mut game, &tt, &TimeLimits::movetime(20), MAX_DEPTH, &SearchOptions::default(), &control, &mut info)
+            });
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            assert!(!search.is_finished());
+            control.ponderhit();
+            let result = search.join().unwrap().unwrap();
+            assert_eq!(result.pv()[0], result.best_move);
+            assert!(result.ponder_move().is_some());
+        });
+    }
+
+    #[test]
+    fn multipv_ranks_distinct_lines() {
+        let mut game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let tt = TranspositionTable::new(1);
+        let mut info = MateInfo { score: 0 };
+        let options = SearchOptions { multipv: 3, ..SearchOptions::default() };
+        let result = think(&mut game, &tt, &TimeLimits::INFINITE, 4, &options,
+                           &SearchControl::new(false), &mut info).unwrap();
+        assert_eq!(result.lines.len(), 3);
+        assert_eq!(result.best_move, result.lines[0].moves[0]);
+        assert!(result.lines.windows(2).all(|w| w[0].score >= w[1].score && w[0].moves[0] != w[1].moves[0]));
+        assert_ne!(result.lines[0].moves[0], result.lines[2].moves[0]);
+        assert!(result.lines.iter().all(|line| line.depth == 4));
+
+        // no more lines than legal moves
+        let mut game = Game::from_fen("7k/8/8/8/8/8/6PP/7K w - - 0 1").unwrap();
+        let options = SearchOptions { multipv: 10, ..SearchOptions::default() };
+        let result = think(&mut game, &tt, &TimeLimits::INFINITE, 3, &options,
+                           &SearchControl::new(false), &mut info).unwrap();
+        assert_eq!(result.lines.len(), 5);
+    }
 }
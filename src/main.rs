@@ -1,70 +1,273 @@
 use std::io;
 use std::io::Write;
 use std::fs::File;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
 
 use rustypawn::ThinkInfo;
-use rustypawn::MoveTrait;
+use rustypawn::Bound;
+use rustypawn::Color;
+use rustypawn::EngineOption;
+use rustypawn::EvalParams;
 use rustypawn::Game;
 use rustypawn::MAX_DEPTH;
+use rustypawn::OptionRegistry;
+use rustypawn::OptionType;
+use rustypawn::SearchControl;
+use rustypawn::SearchInfo;
+use rustypawn::SearchOptions;
+use rustypawn::START_FEN;
+use rustypawn::TimeControl;
+use rustypawn::TimeLimits;
+use rustypawn::TranspositionTable;
 use rustypawn::make_move_algebraic;
 use rustypawn::think;
 
+const OPTIONS: [EngineOption; 21] = [
+    EngineOption { name: "Hash", option_type: OptionType::Spin { min: 1, max: 4096 }, default: "16" },
+    EngineOption { name: "Clear Hash", option_type: OptionType::Button, default: "" },
+    EngineOption { name: "Threads", option_type: OptionType::Spin { min: 1, max: 256 }, default: "1" },
+    EngineOption { name: "MultiPV", option_type: OptionType::Spin { min: 1, max: 256 }, default: "1" },
+    EngineOption { name: "Ponder", option_type: OptionType::Check, default: "false" },
+    EngineOption { name: "Debug Log File", option_type: OptionType::String, default: "" },
+    EngineOption { name: "Null Move Verification", option_type: OptionType::Check, default: "false" },
+    // pruning and reductions, defaults as in SearchOptions
+    EngineOption { name: "Late Move Reductions", option_type: OptionType::Check, default: "true" },
+    EngineOption { name: "LMR Base", option_type: OptionType::Spin { min: 0, max: 300 }, default: "75" },
+    EngineOption { name: "LMR Divisor", option_type: OptionType::Spin { min: 50, max: 1000 }, default: "225" },
+    EngineOption { name: "Late Move Pruning", option_type: OptionType::Check, default: "true" },
+    EngineOption { name: "LMP Base", option_type: OptionType::Spin { min: 0, max: 64 }, default: "3" },
+    EngineOption { name: "Quiescence Checks", option_type: OptionType::Check, default: "false" },
+    EngineOption { name: "Delta Pruning", option_type: OptionType::Check, default: "true" },
+    EngineOption { name: "Delta Margin", option_type: OptionType::Spin { min: 0, max: 1000 }, default: "200" },
+    // evaluation terms, defaults as in EvalParams
+    EngineOption { name: "Doubled Pawn Penalty", option_type: OptionType::Spin { min: 0, max: 200 }, default: "10" },
+    EngineOption { name: "Isolated Pawn Penalty", option_type: OptionType::Spin { min: 0, max: 200 }, default: "20" },
+    EngineOption { name: "Backwards Pawn Penalty", option_type: OptionType::Spin { min: 0, max: 200 }, default: "8" },
+    EngineOption { name: "Passed Pawn Bonus", option_type: OptionType::Spin { min: 0, max: 200 }, default: "20" },
+    EngineOption { name: "Rook Semi Open File Bonus", option_type: OptionType::Spin { min: 0, max: 200 }, default: "10" },
+    EngineOption { name: "Rook Open File Bonus", option_type: OptionType::Spin { min: 0, max: 200 }, default: "15" },
+];
+
+fn search_options(options: &OptionRegistry) -> SearchOptions {
+    SearchOptions {
+        multipv: options.spin("MultiPV") as usize,
+        threads: options.spin("Threads") as usize,
+        null_move_verification: options.check("Null Move Verification"),
+        late_move_reductions: options.check("Late Move Reductions"),
+        lmr_base: options.spin("LMR Base") as usize,
+        lmr_divisor: options.spin("LMR Divisor") as usize,
+        late_move_pruning: options.check("Late Move Pruning"),
+        lmp_base: options.spin("LMP Base") as usize,
+        quiescence_checks: options.check("Quiescence Checks"),
+        delta_pruning: options.check("Delta Pruning"),
+        delta_margin: options.spin("Delta Margin") as usize,
+    }
+}
+
+fn eval_params(options: &OptionRegistry) -> EvalParams {
+    EvalParams {
+        doubled_pawn_penalty: options.spin("Doubled Pawn Penalty") as isize,
+        isolated_pawn_penalty: options.spin("Isolated Pawn Penalty") as isize,
+        backwards_pawn_penalty: options.spin("Backwards Pawn Penalty") as isize,
+        passed_pawn_bonus: options.spin("Passed Pawn Bonus") as isize,
+        rook_semi_open_file_bonus: options.spin("Rook Semi Open File Bonus") as isize,
+        rook_open_file_bonus: options.spin("Rook Open File Bonus") as isize,
+    }
+}
+
+// Shared by the input loop and the search thread.
 struct Comms {
-    file: Option<File>
+    file: Mutex<Option<File>>
 }
 
 impl Comms {
-    pub fn new(name: Option<&str>) -> Comms {
+    pub fn new() -> Comms {
         Comms {
-            file: match name {
-                Some(n) => Some(File::create(n).unwrap()),
-                None => None
-            }
+            file: Mutex::new(None)
         }
     }
-    fn write(self: &mut Comms, prefix: &str, msg: &str) {
-        match &mut self.file {
-            Some(f) => {
-                f.write_all(prefix.as_bytes()).unwrap();
-                f.write_all(msg.as_bytes()).unwrap();
-                f.write_all(b"\n").unwrap();
-            },
-            None => {}
+    // Starts logging to the named file, or stops logging if the name is empty.
+    pub fn set_log_file(self: &Comms, name: &str) -> io::Result<()> {
+        let file = if name.is_empty() { None } else { Some(File::create(name)?) };
+        *self.file.lock().unwrap() = file;
+        Ok(())
+    }
+    fn write(self: &Comms, prefix: &str, msg: &str) {
+        if let Some(f) = &mut *self.file.lock().unwrap() {
+            // logging is best effort, a full disk must not stop the engine
+            let _ = writeln!(f, "{}{}", prefix, msg);
         }
     }
-    pub fn input(self: &mut Comms, msg: &str) {
+    pub fn input(self: &Comms, msg: &str) {
         self.write("> ", msg);
     }
-    pub fn output<S: Into<String>>(self: &mut Comms, msg: S) {
+    pub fn output<S: Into<String>>(self: &Comms, msg: S) {
         let s = msg.into();
         println!("{}", s);
         self.write("< ", &s[..]);
     }
-    pub fn fatal<S: Into<String>>(self: &mut Comms, msg: S) -> ! {
+    // Problems are reported to the GUI and otherwise ignored.
+    pub fn error<S: Into<String>>(self: &Comms, msg: S) {
         let s = msg.into();
         self.write("! ", &s[..]);
-        panic!(s);
+        println!("info string {}", s);
     }
-    pub fn debug<S: Into<String>>(self: &mut Comms, msg: S) {
+    pub fn debug<S: Into<String>>(self: &Comms, msg: S) {
         let s = msg.into();
         self.write("- ", &s[..]);
     }
 }
 
-impl ThinkInfo for Comms {
-    fn think_info(self: &mut Comms, depth: usize, score: isize, mate_in: isize, node_count: usize, millis: u64, moves: &Vec<String>) {
-        let nps = if millis > 0 { 1000 * node_count as u64 / millis } else { 0 };
-        let msg = format!("info depth {} score {} nodes {} time {} nps {} pv {}",
-            depth, if mate_in != 0 { format!("mate {}", mate_in) } else { format!("cp {}", score) },
-            node_count, millis, nps, moves.join(" "));
+impl ThinkInfo for &Comms {
+    fn think_info(&mut self, info: &SearchInfo) {
+        let nps = (1000 * info.node_count as u64).checked_div(info.millis).unwrap_or(0);
+        let moves: Vec<String> = info.pv.iter().map(|m| m.to_algebraic()).collect();
+        let bound = match info.bound {
+            Bound::Exact => "",
+            Bound::Lower => " lowerbound",
+            Bound::Upper => " upperbound",
+        };
+        let msg = format!("info depth {} multipv {} score {}{} nodes {} time {} nps {} pv {}",
+            info.depth, info.multipv,
+            if info.mate_in != 0 { format!("mate {}", info.mate_in) } else { format!("cp {}", info.score) },
+            bound, info.node_count, info.millis, nps, moves.join(" "));
         self.output(msg);
     }
 }
 
+const COMMANDS: [&str; 11] = [
+    "uci", "debug", "isready", "setoption", "register", "ucinewgame", "position", "go", "stop", "ponderhit", "quit"
+];
+
+// The value following a go argument, or None (with a report) if it is
+// missing or malformed.
+fn parse_value<'a, T: std::str::FromStr>(arg_iter: &mut impl Iterator<Item = &'a str>, name: &str,
+                                         comms: &Comms) -> Option<T> {
+    match arg_iter.next() {
+        Some(s) => match s.parse::<T>() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                comms.error(format!("Error parsing {} '{}'", name, s));
+                None
+            }
+        },
+        None => {
+            comms.error(format!("Missing {}", name));
+            None
+        }
+    }
+}
+
+// The position of a 'position' command, or None (with a report) if the FEN
+// or one of the moves is invalid.
+fn set_position<'a>(arg_iter: &mut impl Iterator<Item = &'a str>, comms: &Comms) -> Option<Game> {
+    let fen = match arg_iter.next() {
+        Some("startpos") => {
+            // position startpos
+            // position startpos moves e2e4 e7e5
+            String::from(START_FEN)
+        },
+        Some("fen") => {
+            // position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w moves e2e4 e7e5
+            let mut fen_items: Vec<&str> = Vec::new();
+            loop {
+                match arg_iter.next() {
+                    Some("moves") => break,
+                    Some(s) => fen_items.push(s),
+                    None => break
+                }
+            }
+            fen_items.join(" ")
+        },
+        Some(s) => {
+            comms.error(format!("Unknown 'position' argument '{}'", s));
+            return None;
+        },
+        None => {
+            comms.error("Missing argument to 'position'");
+            return None;
+        }
+    };
+    let mut game = match Game::from_fen(&fen[..]) {
+        Ok(g) => g,
+        Err(e) => {
+            comms.error(format!("Illegal fen string '{}' ({})", fen, e));
+            return None;
+        }
+    };
+    loop {
+        match arg_iter.next() {
+            Some("moves") => continue,
+            Some(s) => {
+                if let Err(e) = make_move_algebraic(&mut game, s) {
+                    comms.error(format!("Move '{}' not played ({})", s, e));
+                    return None;
+                }
+            },
+            None => break
+        }
+    }
+    Some(game)
+}
+
+struct SearchThread {
+    handle: thread::JoinHandle<()>,
+    control: Arc<SearchControl>,
+}
+
+// Searches a copy of the game and reports the best move when done. In
+// infinite mode, and while pondering, the best move is held back until the
+// search is stopped or the ponder move is played.
+fn start_search(game: &Game, tt: &Arc<Mutex<TranspositionTable>>, comms: &Arc<Comms>,
+                limits: TimeLimits, options: SearchOptions, infinite: bool, ponder: bool) -> SearchThread {
+    let control = Arc::new(SearchControl::new(ponder));
+    let mut game = game.clone();
+    let tt = Arc::clone(tt);
+    let comms = Arc::clone(comms);
+    let thread_control = Arc::clone(&control);
+    let handle = thread::spawn(move || {
+        let mut info = &*comms;
+        // a table left behind by a failed search is still usable
+        let tt = tt.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = think(&mut game, &tt, &limits, MAX_DEPTH, &options, &thread_control, &mut info);
+        while (infinite || thread_control.is_pondering()) && !thread_control.is_stopped() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        match result {
+            Some(result) => match result.ponder_move() {
+                Some(ponder_move) => comms.output(format!("bestmove {} ponder {}", result.best_move, ponder_move)),
+                None => comms.output(format!("bestmove {}", result.best_move)),
+            },
+            None => {
+                comms.error("No legal move");
+                comms.output("bestmove 0000");
+            }
+        }
+    });
+    SearchThread { handle, control }
+}
+
+// Stops a running search, which still reports its best move. Should the
+// search thread itself have died, a null move is reported for it.
+fn stop_search(search: &mut Option<SearchThread>, comms: &Comms) {
+    if let Some(SearchThread { handle, control }) = search.take() {
+        control.stop();
+        if handle.join().is_err() {
+            comms.error("Search thread failed");
+            comms.output("bestmove 0000");
+        }
+    }
+}
+
 fn main() {
-    let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0").unwrap();
-    let mut comms = Comms::new(None);
-    // let mut comms = Comms::new("/tmp/rustypawn-0.4.log");
+    // None after an invalid 'position' command, until the next valid one
+    let mut game = Game::from_fen(START_FEN).ok();
+    let comms = Arc::new(Comms::new());
+    let mut options = OptionRegistry::new(&OPTIONS);
+    let tt = Arc::new(Mutex::new(TranspositionTable::new(options.spin("Hash") as usize)));
+    let mut search: Option<SearchThread> = None;
 
     println!("Rustypawn");
 
@@ -76,126 +279,130 @@ fn main() {
                 let line = input.trim();
                 comms.input(line);
                 let mut arg_iter = line.split_whitespace();
-                match arg_iter.next() {
+                // unknown tokens before the command are skipped
+                match arg_iter.find(|token| COMMANDS.contains(token)) {
                     Some("uci") => {
                         comms.output("id name rustypawn");
                         comms.output("id author Jan Marthedal Rasmussen");
+                        for line in options.uci_lines() {
+                            comms.output(line);
+                        }
                         comms.output("uciok");
                     },
                     Some("isready") => {
                         comms.output("readyok");
                     },
-                    Some("position") => {
-                        let fen = match arg_iter.next() {
-                            Some("startpos") => {
-                                // position startpos
-                                // position startpos moves e2e4 e7e5
-                                String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0")
-                            },
-                            Some("fen") => {
-                                // position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w moves e2e4 e7e5
-                                let mut fen_items: Vec<&str> = Vec::new();
-                                loop {
-                                    match arg_iter.next() {
-                                        Some("moves") => break,
-                                        Some(s) => fen_items.push(s),
-                                        None => break
-                                    }
+                    Some("setoption") => {
+                        // setoption name MultiPV value 3
+                        // setoption name Clear Hash
+                        stop_search(&mut search, &comms);
+                        if arg_iter.next() != Some("name") {
+                            comms.error("Missing option name");
+                            continue;
+                        }
+                        let name: Vec<&str> = arg_iter.by_ref().take_while(|&word| word != "value").collect();
+                        let value: Vec<&str> = arg_iter.collect();
+                        match options.set(&name.join(" "), &value.join(" ")) {
+                            Ok("Hash") => tt.lock().unwrap_or_else(PoisonError::into_inner).resize(options.spin("Hash") as usize),
+                            Ok("Clear Hash") => tt.lock().unwrap_or_else(PoisonError::into_inner).clear(),
+                            Ok("Debug Log File") => {
+                                let name = options.string("Debug Log File");
+                                if let Err(e) = comms.set_log_file(name) {
+                                    comms.error(format!("Cannot open log file '{}' ({})", name, e));
                                 }
-                                fen_items.join(" ")
                             },
-                            Some(_) => comms.fatal("Unknown 'position' argument"),
-                            _ => comms.fatal("Missing argument to 'position'")
-                        };
-                        game = match Game::from_fen(&fen[..]) {
-                            Ok(g) => g,
-                            Err(e) => comms.fatal(format!("Illegal fen string '{}' ({})", fen, e))
-                        };
-                        loop {
-                            match arg_iter.next() {
-                                Some("moves") => continue,
-                                Some(s) => make_move_algebraic(&mut game, s),
-                                None => break
-                            }
+                            // other options are read when they are needed
+                            Ok(_) => {},
+                            Err(e) => comms.error(format!("Option not set ({})", e)),
                         }
                     },
+                    Some("ucinewgame") => {
+                        stop_search(&mut search, &comms);
+                        tt.lock().unwrap_or_else(PoisonError::into_inner).clear();
+                    },
+                    Some("position") => {
+                        stop_search(&mut search, &comms);
+                        game = set_position(&mut arg_iter, &comms);
+                    },
                     Some("go") => {
-                        let white_to_move = game.white_to_move();
-                        let mut millis_to_think: u64 = 10 * 60 * 1000;  // 10 minutes
-                        let mut wtime: i32 = -1;
-                        let mut btime: i32 = -1;
-                        let mut movestogo: u64 = 0;
+                        stop_search(&mut search, &comms);
+                        let game = match &mut game {
+                            Some(game) => game,
+                            None => {
+                                comms.error("No valid position to search");
+                                comms.output("bestmove 0000");
+                                continue;
+                            }
+                        };
+                        let mut infinite = false;
+                        let mut ponder = false;
+                        let mut tc = TimeControl::default();
+                        // the clock of the side to move was given but not understood
+                        let mut bad_clock = false;
                         loop {
+                            // clock times may be negative when a GUI lets the engine overstep
                             match arg_iter.next() {
                                 Some("wtime") => {
-                                    match arg_iter.next() {
-                                        Some(s) => {
-                                            wtime = match s.parse::<i32>() {
-                                                Ok(n) => n,
-                                                _ => comms.fatal("Error parsing wtime")
-                                            };
-                                            if white_to_move && movestogo > 0 {
-                                                millis_to_think = wtime as u64 / movestogo;
-                                            }
-                                        },
-                                        None => comms.fatal("Missing wtime")
-                                    }
+                                    tc.wtime = parse_value::<i64>(&mut arg_iter, "wtime", &comms).map(|n| n.max(0) as u64);
+                                    bad_clock |= tc.wtime.is_none() && game.side_to_move() == Color::White;
                                 },
                                 Some("btime") => {
-                                    match arg_iter.next() {
-                                        Some(s) => {
-                                            btime = match s.parse::<i32>() {
-                                                Ok(n) => n,
-                                                _ => comms.fatal("Error parsing btime")
-                                            };
-                                            if !white_to_move && movestogo > 0 {
-                                                millis_to_think = btime as u64 / movestogo;
-                                            }
-                                        },
-                                        None => comms.fatal("Missing btime")
-                                    }
+                                    tc.btime = parse_value::<i64>(&mut arg_iter, "btime", &comms).map(|n| n.max(0) as u64);
+                                    bad_clock |= tc.btime.is_none() && game.side_to_move() == Color::Black;
+                                },
+                                Some("winc") => {
+                                    tc.winc = parse_value::<i64>(&mut arg_iter, "winc", &comms).map_or(0, |n| n.max(0) as u64);
+                                },
+                                Some("binc") => {
+                                    tc.binc = parse_value::<i64>(&mut arg_iter, "binc", &comms).map_or(0, |n| n.max(0) as u64);
                                 },
                                 Some("movestogo") => {
-                                    match arg_iter.next() {
-                                        Some(s) => {
-                                            movestogo = match s.parse::<u64>() {
-                                                Ok(n) => n,
-                                                _ => comms.fatal("Error parsing movestogo")
-                                            };
-                                            if white_to_move && wtime > 0 {
-                                                millis_to_think = wtime as u64 / movestogo;
-                                            } else if !white_to_move && btime > 0 {
-                                                millis_to_think = btime as u64 / movestogo;
-                                            }
-                                        },
-                                        None => comms.fatal("Missing movestogo")
-                                    }
+                                    tc.movestogo = parse_value(&mut arg_iter, "movestogo", &comms);
                                 },
                                 Some("movetime") => {
-                                    match arg_iter.next() {
-                                        Some(s) => {
-                                            millis_to_think = match s.parse::<u64>() {
-                                                Ok(n) => n,
-                                                _ => comms.fatal("Error parsing movetime")
-                                            };
-                                        },
-                                        None => comms.fatal("Missing movetime")
-                                    }
+                                    tc.movetime = parse_value(&mut arg_iter, "movetime", &comms);
+                                },
+                                Some("infinite") => {
+                                    infinite = true;
+                                },
+                                Some("ponder") => {
+                                    ponder = true;
                                 },
                                 Some(s) => comms.debug(format!("Ignore go argument '{}'", s)),
                                 None => break
                             }
                         }
-                        let mv = match think(&mut game, millis_to_think, MAX_DEPTH, &mut comms) {
-                            Some(m) => m,
-                            None => comms.fatal("No legal move")
+                        let limits = if infinite {
+                            TimeLimits::INFINITE
+                        } else {
+                            TimeLimits::new(&tc, game.side_to_move())
                         };
-                        comms.output(format!("bestmove {}", mv.to_algebraic()));
-                    }
+                        if bad_clock && !infinite && tc.movetime.is_none() {
+                            comms.error(format!("No valid clock time, searching for {} ms", limits.hard));
+                        }
+                        comms.debug(format!("Time limits {} ms soft, {} ms hard", limits.soft, limits.hard));
+                        game.set_eval_params(eval_params(&options));
+                        let search_options = search_options(&options);
+                        search = Some(start_search(game, &tt, &comms, limits, search_options, infinite, ponder));
+                    },
+                    Some("stop") => {
+                        stop_search(&mut search, &comms);
+                    },
+                    Some("ponderhit") => {
+                        // the search goes on, now with the clock running
+                        if let Some(SearchThread { control, .. }) = &search {
+                            control.ponderhit();
+                        }
+                    },
+                    Some("quit") => break,
                     _ => continue
                 }
             }
-            Err(error) => comms.fatal(format!("error: {}", error))
+            Err(error) => {
+                comms.error(format!("error: {}", error));
+                break;
+            }
         }
     }
+    stop_search(&mut search, &comms);
 }
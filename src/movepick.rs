@@ -0,0 +1,215 @@
+use crate::{Game, Move, OrderingHints, ScoredMove, DUMMY_MOVE, ORDER_GOOD_CAPTURE};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Stage {
+    FirstMoves,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// Hands out the pseudo-legal moves of a position in stages, generating and
+// scoring each kind only when the earlier ones did not cause a cutoff: the
+// principal variation and hash moves, captures that do not lose material,
+// killers and the countermove, quiet moves by history and last the losing
+// captures. Within a stage the best scored move is selected each time
+// instead of sorting them all.
+pub struct MovePicker {
+    stage: Stage,
+    first_moves: [Move; 2],  // the principal variation and hash moves
+    refutations: [Move; 3],  // killers and the countermove
+    captures_only: bool,
+    checks: bool,  // with captures_only, quiet moves giving check too
+    moves: Vec<ScoredMove>,
+    bad_captures: Vec<ScoredMove>,
+    index: usize,
+}
+
+impl MovePicker {
+
+    pub fn new(pv_move: Move, hash_move: Move, killers: [Move; 2], countermove: Move) -> MovePicker {
+        MovePicker {
+            stage: Stage::FirstMoves,
+            first_moves: [pv_move, hash_move],
+            refutations: [killers[0], killers[1], countermove],
+            captures_only: false,
+            checks: false,
+            moves: Vec::new(),
+            bad_captures: Vec::new(),
+            index: 0,
+        }
+    }
+
+    // For quiescence search: captures that do not lose material, after the
+    // principal variation and hash moves if those are such captures too.
+    pub fn captures(pv_move: Move, hash_move: Move) -> MovePicker {
+        MovePicker {
+            captures_only: true,
+            ..MovePicker::new(pv_move, hash_move, [DUMMY_MOVE; 2], DUMMY_MOVE)
+        }
+    }
+
+    // The same followed by the quiet moves that give check.
+    pub fn captures_and_checks(pv_move: Move, hash_move: Move) -> MovePicker {
+        MovePicker {
+            checks: true,
+            ..MovePicker::captures(pv_move, hash_move)
+        }
+    }
+
+    // Whether a pseudo-legal move from outside belongs in this picker's
+    // moves: in quiescence search a capture must not lose material.
+    fn wanted(self: &MovePicker, game: &Game, mv: Move) -> bool {
+        if !self.captures_only {
+            return true;
+        }
+        if game.is_quiet(mv) { self.checks && game.gives_check(mv) } else { game.see(mv) >= 0 }
+    }
+
+    // The highest scored of the remaining moves, or None when all are taken.
+    fn select(moves: &mut [ScoredMove], index: &mut usize) -> Option<Move> {
+        let best = (*index..moves.len()).max_by_key(|&i| moves[i].score)?;
+        moves.swap(*index, best);
+        *index += 1;
+        Some(moves[*index - 1].mv)
+    }
+
+    fn scored(game: &Game, moves: &[Move], history: &[i32; 64 * 64]) -> Vec<ScoredMove> {
+        let hints = OrderingHints {
+            pv_move: DUMMY_MOVE,
+            hash_move: DUMMY_MOVE,
+            killers: [DUMMY_MOVE; 2],
+            countermove: DUMMY_MOVE,
+            history,
+        };
+        game.score_moves(moves, &hints)
+    }
+
+    // The next move to search; history is that of the side to move.
+    pub fn next(self: &mut MovePicker, game: &Game, history: &[i32; 64 * 64]) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::FirstMoves => {
+                    while self.index < 2 {
+                        let mv = self.first_moves[self.index];
+                        self.index += 1;
+                        if mv != DUMMY_MOVE && !self.first_moves[..self.index - 1].contains(&mv)
+                                && game.is_pseudo_legal(mv) && self.wanted(game, mv) {
+                            return Some(mv);
+                        }
+                        // not picked, so left in the generated moves
+                        self.first_moves[self.index - 1] = DUMMY_MOVE;
+                    }
+                    self.stage = Stage::GenerateCaptures;
+                },
+                Stage::GenerateCaptures => {
+                    let mut captures = game.capture_moves();
+                    captures.retain(|mv| !self.first_moves.contains(mv));
+                    let (good, bad) = MovePicker::scored(game, &captures, history).into_iter()
+                        .partition(|m| m.score >= ORDER_GOOD_CAPTURE);
+                    self.moves = good;
+                    self.bad_captures = bad;
+                    self.index = 0;
+                    self.stage = Stage::GoodCaptures;
+                },
+                Stage::GoodCaptures => {
+                    if let Some(mv) = MovePicker::select(&mut self.moves, &mut self.index) {
+                        return Some(mv);
+                    }
+                    self.index = 0;
+                    self.stage = match (self.captures_only, self.checks) {
+                        (false, _) => Stage::Killers,
+                        (true, true) => Stage::GenerateQuiets,
+                        (true, false) => Stage::Done,
+                    };
+                },
+                Stage::Killers => {
+                    while self.index < 3 {
+                        let mv = self.refutations[self.index];
+                        self.index += 1;
+                        if mv != DUMMY_MOVE && !self.first_moves.contains(&mv)
+                                && !self.refutations[..self.index - 1].contains(&mv)
+                                && game.is_quiet(mv) && game.is_pseudo_legal(mv) {
+                            return Some(mv);
+                        }
+                    }
+                    self.stage = Stage::GenerateQuiets;
+                },
+                Stage::GenerateQuiets => {
+                    // promotions without capture come along here, scored ahead
+                    // of the real quiet moves
+                    let mut quiets = game.quiet_moves();
+                    quiets.retain(|&mv| !self.first_moves.contains(&mv) && !self.refutations.contains(&mv)
+                                        && (!self.captures_only || (game.is_quiet(mv) && game.gives_check(mv))));
+                    self.moves = MovePicker::scored(game, &quiets, history);
+                    self.index = 0;
+                    self.stage = Stage::Quiets;
+                },
+                Stage::Quiets => {
+                    if let Some(mv) = MovePicker::select(&mut self.moves, &mut self.index) {
+                        return Some(mv);
+                    }
+                    self.index = 0;
+                    self.stage = if self.captures_only { Stage::Done } else { Stage::BadCaptures };
+                },
+                Stage::BadCaptures => {
+                    if let Some(mv) = MovePicker::select(&mut self.bad_captures, &mut self.index) {
+                        return Some(mv);
+                    }
+                    self.stage = Stage::Done;
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebraic_to_move;
+
+    fn picked(game: &Game, mut picker: MovePicker) -> Vec<Move> {
+        let history = [0; 64 * 64];
+        let mut moves = Vec::new();
+        while let Some(mv) = picker.next(game, &history) {
+            moves.push(mv);
+        }
+        moves
+    }
+
+    #[test]
+    fn picks_every_move_once() {
+        let game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mv = |s: &str| algebraic_to_move(s).unwrap();
+        // a hash move that does not fit the position is left out
+        let picker = MovePicker::new(mv("e2a6"), mv("a1a8"), [mv("a2a3"), mv("e5c4")], mv("a2a3"));
+        let moves = picked(&game, picker);
+        assert_eq!(moves[0], mv("e2a6"));
+        // then the captures winning or trading material, and the killers
+        let good_captures = moves.iter().skip(1).take_while(|&&m| !game.is_quiet(m)).count();
+        assert!(moves[1..=good_captures].iter().all(|&m| game.see(m) >= 0));
+        assert_eq!(moves[good_captures + 1..good_captures + 3], [mv("a2a3"), mv("e5c4")]);
+        let mut sorted = moves.clone();
+        sorted.sort_by_key(|m| m.0);
+        sorted.dedup();
+        assert_eq!(sorted.len(), moves.len());
+        let mut generated = game.generate_moves();
+        generated.sort_by_key(|m| m.0);
+        assert_eq!(sorted, generated);
+
+        // quiescence leaves out quiet and losing moves, even from the hash table
+        assert!(game.see(mv("f3f6")) < 0);
+        for hash_move in ["a2a3", "f3f6"].iter() {
+            let captures = picked(&game, MovePicker::captures(DUMMY_MOVE, mv(hash_move)));
+            assert!(captures.iter().all(|&m| !game.is_quiet(m) && game.see(m) >= 0));
+            assert_eq!(captures.len(), good_captures + 1);
+        }
+        let captures = picked(&game, MovePicker::captures(mv("f3f6"), mv("f3f6")));
+        assert!(!captures.contains(&mv("f3f6")));
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+// The UCI option types. Spin ranges are inclusive.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OptionType {
+    Spin { min: i64, max: i64 },
+    Check,
+    Combo(&'static [&'static str]),
+    String,
+    Button,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EngineOption {
+    pub name: &'static str,
+    pub option_type: OptionType,
+    pub default: &'static str,  // empty for buttons
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum OptionError {
+    Unknown(String),
+    InvalidValue(String, String),  // option name and value
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionError::Unknown(name) => write!(f, "unknown option '{}'", name),
+            OptionError::InvalidValue(name, value) => write!(f, "illegal value '{}' for option '{}'", value, name),
+        }
+    }
+}
+
+// Engine options with their current values, kept as text in canonical form
+// ("true"/"false" for checks, the listed spelling for combos).
+pub struct OptionRegistry {
+    options: Vec<(EngineOption, String)>,
+}
+
+impl OptionRegistry {
+
+    pub fn new(options: &[EngineOption]) -> OptionRegistry {
+        OptionRegistry {
+            options: options.iter().map(|option| (*option, option.default.to_string())).collect()
+        }
+    }
+
+    // The 'option' lines of the uci response.
+    pub fn uci_lines(self: &OptionRegistry) -> Vec<String> {
+        self.options.iter().map(|(option, _)| {
+            let mut line = format!("option name {} type ", option.name);
+            match option.option_type {
+                OptionType::Spin { min, max } =>
+                    line.push_str(&format!("spin default {} min {} max {}", option.default, min, max)),
+                OptionType::Check => line.push_str(&format!("check default {}", option.default)),
+                OptionType::Combo(vars) => {
+                    line.push_str(&format!("combo default {}", option.default));
+                    for var in vars.iter() {
+                        line.push_str(&format!(" var {}", var));
+                    }
+                },
+                OptionType::String => line.push_str(&format!("string default {}",
+                    if option.default.is_empty() { "<empty>" } else { option.default })),
+                OptionType::Button => line.push_str("button"),
+            }
+            line
+        }).collect()
+    }
+
+    // Option names are case insensitive, as are check and combo values.
+    // Returns the canonical name of the option.
+    pub fn set(self: &mut OptionRegistry, name: &str, value: &str) -> Result<&'static str, OptionError> {
+        let (option, current) = self.options.iter_mut()
+            .find(|(option, _)| option.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| OptionError::Unknown(name.to_string()))?;
+        let invalid = || OptionError::InvalidValue(option.name.to_string(), value.to_string());
+        *current = match option.option_type {
+            OptionType::Spin { min, max } => match value.parse::<i64>() {
+                Ok(n) if n >= min && n <= max => n.to_string(),
+                _ => return Err(invalid()),
+            },
+            OptionType::Check => match value.to_ascii_lowercase().as_str() {
+                "true" => String::from("true"),
+                "false" => String::from("false"),
+                _ => return Err(invalid()),
+            },
+            OptionType::Combo(vars) => match vars.iter().find(|var| var.eq_ignore_ascii_case(value)) {
+                Some(var) => var.to_string(),
+                None => return Err(invalid()),
+            },
+            OptionType::String => if value == "<empty>" { String::new() } else { value.to_string() },
+            OptionType::Button => String::new(),
+        };
+        Ok(option.name)
+    }
+
+    fn value(self: &OptionRegistry, name: &str) -> &str {
+        match self.options.iter().find(|(option, _)| option.name == name) {
+            Some((_, value)) => value,
+            None => panic!("option '{}' is not registered", name),
+        }
+    }
+
+    pub fn spin(self: &OptionRegistry, name: &str) -> i64 {
+        self.value(name).parse().unwrap_or(0)
+    }
+
+    pub fn check(self: &OptionRegistry, name: &str) -> bool {
+        self.value(name) == "true"
+    }
+
+    pub fn string(self: &OptionRegistry, name: &str) -> &str {
+        self.value(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: [EngineOption; 5] = [
+        EngineOption { name: "Hash", option_type: OptionType::Spin { min: 1, max: 1024 }, default: "16" },
+        EngineOption { name: "Ponder", option_type: OptionType::Check, default: "false" },
+        EngineOption { name: "Style", option_type: OptionType::Combo(&["Solid", "Risky"]), default: "Solid" },
+        EngineOption { name: "Debug Log File", option_type: OptionType::String, default: "" },
+        EngineOption { name: "Clear Hash", option_type: OptionType::Button, default: "" },
+    ];
+
+    #[test]
+    fn advertises_options() {
+        assert_eq!(OptionRegistry::new(&OPTIONS).uci_lines(), vec![
+            "option name Hash type spin default 16 min 1 max 1024",
+            "option name Ponder type check default false",
+            "option name Style type combo default Solid var Solid var Risky",
+            "option name Debug Log File type string default <empty>",
+            "option name Clear Hash type button",
+        ]);
+    }
+
+    #[test]
+    fn sets_options() {
+        let mut options = OptionRegistry::new(&OPTIONS);
+        assert_eq!(options.spin("Hash"), 16);
+        assert_eq!(options.set("hash", "64"), Ok("Hash"));
+        assert_eq!(options.spin("Hash"), 64);
+        assert_eq!(options.set("Hash", "0"), Err(OptionError::InvalidValue(String::from("Hash"), String::from("0"))));
+        assert_eq!(options.set("Hash", "lots").unwrap_err().to_string(), "illegal value 'lots' for option 'Hash'");
+        assert_eq!(options.spin("Hash"), 64);
+        assert_eq!(options.set("Ponder", "True"), Ok("Ponder"));
+        assert!(options.check("Ponder"));
+        assert_eq!(options.set("style", "risky"), Ok("Style"));
+        assert_eq!(options.string("Style"), "Risky");
+        assert!(options.set("Style", "Wild").is_err());
+        assert_eq!(options.set("debug log file", "/tmp/engine log.txt"), Ok("Debug Log File"));
+        assert_eq!(options.string("Debug Log File"), "/tmp/engine log.txt");
+        assert_eq!(options.set("Clear Hash", ""), Ok("Clear Hash"));
+        assert_eq!(options.set("Contempt", "10"), Err(OptionError::Unknown(String::from("Contempt"))));
+    }
+}
@@ -0,0 +1,584 @@
+use std::fmt;
+
+use crate::{Bound, Color, FenError, Game, GameStatus, Move, SanError, SearchInfo, ThinkInfo, START_FEN};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PgnErrorKind {
+    UnterminatedTag,
+    UnterminatedComment,
+    UnbalancedVariation,
+    UnexpectedToken(String),
+    InvalidFen(FenError),
+    Move(String, SanError),
+}
+
+// Games and plies are counted from 1; ply 0 means the error is in the tag
+// section or before the first move. For syntax errors the ply is that of the
+// last main line move read.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PgnError {
+    pub game: usize,
+    pub ply: usize,
+    pub kind: PgnErrorKind,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "game {}, ply {}: ", self.game, self.ply)?;
+        match &self.kind {
+            PgnErrorKind::UnterminatedTag => f.write_str("unterminated tag pair"),
+            PgnErrorKind::UnterminatedComment => f.write_str("unterminated comment"),
+            PgnErrorKind::UnbalancedVariation => f.write_str("unbalanced variation"),
+            PgnErrorKind::UnexpectedToken(token) => write!(f, "unexpected '{}'", token),
+            PgnErrorKind::InvalidFen(err) => write!(f, "FEN tag: {}", err),
+            PgnErrorKind::Move(san, err) => write!(f, "{}: {}", san, err),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct PgnMove {
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,          // comments following the move
+    pub variations: Vec<Vec<PgnMove>>,  // alternatives to this move
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PgnGame {
+    pub number: usize,
+    pub tags: Vec<(String, String)>,
+    pub comments: Vec<String>,  // comments before the first move
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+impl PgnGame {
+
+    pub fn tag(self: &PgnGame, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    fn error(self: &PgnGame, ply: usize, kind: PgnErrorKind) -> PgnError {
+        PgnError { game: self.number, ply, kind }
+    }
+
+    // The position given by the FEN tag, or the standard starting position.
+    pub fn start_position(self: &PgnGame) -> Result<Game, PgnError> {
+        Game::from_fen(self.tag("FEN").unwrap_or(START_FEN))
+            .map_err(|err| self.error(0, PgnErrorKind::InvalidFen(err)))
+    }
+
+    // Plays the main line, returning the final position and the moves made.
+    pub fn replay(self: &PgnGame) -> Result<(Game, Vec<Move>), PgnError> {
+        let mut game = self.start_position()?;
+        let mut moves = Vec::with_capacity(self.moves.len());
+        for (k, pgn_move) in self.moves.iter().enumerate() {
+            let mv = game.parse_san(&pgn_move.san)
+                .map_err(|err| self.error(k + 1, PgnErrorKind::Move(pgn_move.san.clone(), err)))?;
+            game.make_move(mv);
+            moves.push(mv);
+        }
+        Ok((game, moves))
+    }
+}
+
+fn is_result(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+// Traditional suffix annotations and their NAG equivalents.
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "[]{}();$".contains(c)
+}
+
+// Iterates over the games of a PGN text. After an error the reader skips to
+// the next tag section, so one broken game does not hide the others.
+pub struct PgnReader<'a> {
+    text: &'a str,
+    pos: usize,
+    number: usize,
+}
+
+impl<'a> PgnReader<'a> {
+
+    pub fn new(text: &'a str) -> PgnReader<'a> {
+        PgnReader { text, pos: 0, number: 0 }
+    }
+
+    fn peek(self: &PgnReader<'a>) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(self: &mut PgnReader<'a>) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn at_line_start(self: &PgnReader<'a>) -> bool {
+        self.pos == 0 || self.text[..self.pos].ends_with('\n')
+    }
+
+    fn skip_line(self: &mut PgnReader<'a>) {
+        match self.text[self.pos..].find('\n') {
+            Some(n) => self.pos += n + 1,
+            None => self.pos = self.text.len(),
+        }
+    }
+
+    // Skips white space and '%' escape lines.
+    fn skip_space(self: &mut PgnReader<'a>) {
+        while let Some(c) = self.peek() {
+            if c == '%' && self.at_line_start() {
+                self.skip_line();
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // Skips the rest of a broken game, including its remaining tag pairs if
+    // the error was in the tag section.
+    fn recover(self: &mut PgnReader<'a>, in_tags: bool) {
+        if in_tags {
+            while self.text[self.pos..].starts_with('[') {
+                self.skip_line();
+            }
+        }
+        loop {
+            self.skip_line();
+            if self.pos >= self.text.len() || self.text[self.pos..].starts_with('[') {
+                break;
+            }
+        }
+    }
+
+    fn read_symbol(self: &mut PgnReader<'a>) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if is_delimiter(c) {
+                break;
+            }
+            self.bump();
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn read_tag(self: &mut PgnReader<'a>) -> Result<(String, String), PgnErrorKind> {
+        self.bump();
+        self.skip_space();
+        let name = self.read_symbol().to_string();
+        self.skip_space();
+        if name.is_empty() || self.bump() != Some('"') {
+            return Err(PgnErrorKind::UnterminatedTag);
+        }
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('\\') => match self.bump() {
+                    Some(c) => value.push(c),
+                    None => return Err(PgnErrorKind::UnterminatedTag),
+                },
+                Some('"') => break,
+                Some('\n') | None => return Err(PgnErrorKind::UnterminatedTag),
+                Some(c) => value.push(c),
+            }
+        }
+        self.skip_space();
+        if self.bump() != Some(']') {
+            return Err(PgnErrorKind::UnterminatedTag);
+        }
+        Ok((name, value))
+    }
+
+    fn read_comment(self: &mut PgnReader<'a>) -> Result<String, PgnErrorKind> {
+        if self.bump() == Some(';') {
+            let start = self.pos;
+            self.skip_line();
+            return Ok(self.text[start..self.pos].trim().to_string());
+        }
+        match self.text[self.pos..].find('}') {
+            Some(n) => {
+                let comment = self.text[self.pos..self.pos + n].trim().to_string();
+                self.pos += n + 1;
+                Ok(comment)
+            },
+            None => Err(PgnErrorKind::UnterminatedComment),
+        }
+    }
+
+    // Reads a move sequence up to the end of a variation (when nested) or
+    // the game termination marker, which is returned if present.
+    fn read_moves(self: &mut PgnReader<'a>, moves: &mut Vec<PgnMove>, comments: &mut Vec<String>,
+                  nested: bool) -> Result<Option<String>, PgnErrorKind> {
+        loop {
+            self.skip_space();
+            let c = match self.peek() {
+                Some(c) => c,
+                None if nested => return Err(PgnErrorKind::UnbalancedVariation),
+                None => return Ok(None),
+            };
+            match c {
+                '{' | ';' => {
+                    let comment = self.read_comment()?;
+                    match moves.last_mut() {
+                        Some(mv) => mv.comments.push(comment),
+                        None => comments.push(comment),
+                    }
+                },
+                '(' => {
+                    self.bump();
+                    let mut variation = Vec::new();
+                    let mut leading = Vec::new();
+                    self.read_moves(&mut variation, &mut leading, true)?;
+                    match moves.last_mut() {
+                        Some(mv) => mv.variations.push(variation),
+                        None => return Err(PgnErrorKind::UnexpectedToken(String::from("("))),
+                    }
+                },
+                ')' => {
+                    self.bump();
+                    if nested {
+                        return Ok(None);
+                    }
+                    return Err(PgnErrorKind::UnbalancedVariation);
+                },
+                '$' => {
+                    self.bump();
+                    let token = self.read_symbol();
+                    match (token.parse::<u8>(), moves.last_mut()) {
+                        (Ok(nag), Some(mv)) => mv.nags.push(nag),
+                        _ => return Err(PgnErrorKind::UnexpectedToken(format!("${}", token))),
+                    }
+                },
+                '[' if !nested => return Ok(None),
+                '[' | ']' => return Err(PgnErrorKind::UnexpectedToken(c.to_string())),
+                _ => {
+                    let token = self.read_symbol();
+                    if is_result(token) {
+                        if nested {
+                            return Err(PgnErrorKind::UnbalancedVariation);
+                        }
+                        return Ok(Some(token.to_string()));
+                    }
+                    // move numbers such as "12.", "12..." or "12.e4"
+                    let mut san = token;
+                    let digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+                    if digits.is_empty() || digits.starts_with('.') {
+                        san = digits.trim_start_matches('.');
+                        if san.is_empty() {
+                            continue;
+                        }
+                    }
+                    let end = san.trim_end_matches(['!', '?'].as_ref()).len();
+                    let mut pgn_move = PgnMove { san: san[..end].to_string(), ..PgnMove::default() };
+                    if end < san.len() {
+                        match suffix_nag(&san[end..]) {
+                            Some(nag) => pgn_move.nags.push(nag),
+                            None => return Err(PgnErrorKind::UnexpectedToken(token.to_string())),
+                        }
+                    }
+                    if pgn_move.san.is_empty() {
+                        return Err(PgnErrorKind::UnexpectedToken(token.to_string()));
+                    }
+                    moves.push(pgn_move);
+                },
+            }
+        }
+    }
+
+    fn read_game(self: &mut PgnReader<'a>, game: &mut PgnGame) -> Result<(), PgnErrorKind> {
+        while self.peek() == Some('[') {
+            let tag = self.read_tag()?;
+            game.tags.push(tag);
+            self.skip_space();
+        }
+        let result = self.read_moves(&mut game.moves, &mut game.comments, false)?;
+        // without a termination marker, fall back on the Result tag
+        game.result = match result.as_deref().or_else(|| game.tag("Result")) {
+            Some(r) if is_result(r) => r.to_string(),
+            _ => String::from("*"),
+        };
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for PgnReader<'a> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Result<PgnGame, PgnError>> {
+        self.skip_space();
+        self.peek()?;
+        self.number += 1;
+        let mut game = PgnGame {
+            number: self.number,
+            tags: Vec::new(),
+            comments: Vec::new(),
+            moves: Vec::new(),
+            result: String::new(),
+        };
+        match self.read_game(&mut game) {
+            Ok(()) => Some(Ok(game)),
+            Err(kind) => {
+                self.recover(kind == PgnErrorKind::UnterminatedTag);
+                Some(Err(game.error(game.moves.len(), kind)))
+            }
+        }
+    }
+}
+
+// Reads all games, stopping at the first error.
+pub fn read_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    PgnReader::new(text).collect()
+}
+
+// The engine's verdict on a position, as reported through ThinkInfo. Using it
+// as the ThinkInfo of a search records the last (deepest) report.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PgnEval {
+    pub depth: usize,
+    pub score: isize,    // centipawns, from the side to move
+    pub mate_in: isize,  // non-zero when the score is a mate
+}
+
+impl ThinkInfo for PgnEval {
+    fn think_info(&mut self, info: &SearchInfo) {
+        if info.multipv == 1 && info.bound == Bound::Exact {
+            *self = PgnEval { depth: info.depth, score: info.score, mate_in: info.mate_in };
+        }
+    }
+}
+
+impl PgnEval {
+    // The %eval command value, from White's point of view.
+    fn annotation(self: &PgnEval, side: Color) -> String {
+        let sign = if side == Color::White { 1 } else { -1 };
+        if self.mate_in != 0 {
+            format!("[%eval #{}]", sign * self.mate_in)
+        } else {
+            let score = sign * self.score;
+            format!("[%eval {}{}.{:02}]", if score < 0 { "-" } else { "" }, score.abs() / 100, score.abs() % 100)
+        }
+    }
+}
+
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"), ("Site", "?"), ("Date", "????.??.??"), ("Round", "?"),
+    ("White", "?"), ("Black", "?"), ("Result", "*"),
+];
+
+fn status_result(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Ongoing => "*",
+        GameStatus::Checkmate(Color::White) => "1-0",
+        GameStatus::Checkmate(Color::Black) => "0-1",
+        _ => "1/2-1/2",
+    }
+}
+
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Appends a movetext token, wrapping lines at 80 characters.
+fn push_token(movetext: &mut String, line_length: &mut usize, token: &str) {
+    if *line_length > 0 && *line_length + 1 + token.len() > 80 {
+        movetext.push('\n');
+        *line_length = 0;
+    } else if *line_length > 0 {
+        movetext.push(' ');
+        *line_length += 1;
+    }
+    movetext.push_str(token);
+    *line_length += token.len();
+}
+
+// Writes a game played from the given position. The seven tag roster is
+// always present, with values from `tags` where given and further tags after
+// it; without a Result tag the result follows from the final position. The
+// SetUp and FEN tags are written when the game does not start from the
+// standard position. `evals` holds optional evaluations made before each move. The game is left
+// in its starting position.
+pub fn write_pgn(game: &mut Game, tags: &[(&str, &str)], moves: &[Move],
+                 evals: &[Option<PgnEval>]) -> Result<String, PgnError> {
+    let mut movetext = String::new();
+    let mut line_length = 0;
+    let mut played = 0;
+    let mut error = None;
+    let start_fen = game.to_fen();
+    for (k, &mv) in moves.iter().enumerate() {
+        if !game.legal_moves().contains(&mv) {
+            error = Some(PgnError { game: 1, ply: k + 1, kind: PgnErrorKind::Move(mv.to_string(), SanError::Illegal) });
+            break;
+        }
+        let side = game.side_to_move();
+        if side == Color::White {
+            push_token(&mut movetext, &mut line_length, &format!("{}.", game.fullmove_number()));
+        } else if k == 0 {
+            push_token(&mut movetext, &mut line_length, &format!("{}...", game.fullmove_number()));
+        }
+        let san = game.move_to_san(mv);
+        push_token(&mut movetext, &mut line_length, &san);
+        if let Some(Some(eval)) = evals.get(k) {
+            push_token(&mut movetext, &mut line_length, &format!("{{{}}}", eval.annotation(side)));
+        }
+        game.make_move(mv);
+        played += 1;
+    }
+    let result = match tags.iter().find(|(name, _)| *name == "Result") {
+        Some((_, value)) => value.to_string(),
+        None => status_result(game.status()).to_string(),
+    };
+    for &mv in moves[..played].iter().rev() {
+        game.unmake_move(mv);
+    }
+    if let Some(err) = error {
+        return Err(err);
+    }
+    push_token(&mut movetext, &mut line_length, &result);
+
+    let mut pgn = String::new();
+    for (name, default) in SEVEN_TAG_ROSTER.iter() {
+        let value = match *name {
+            "Result" => result.as_str(),
+            _ => tags.iter().find(|(n, _)| n == name).map_or(*default, |(_, v)| *v),
+        };
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag(value)));
+    }
+    if start_fen != START_FEN {
+        pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", start_fen));
+    }
+    for (name, value) in tags.iter() {
+        if !SEVEN_TAG_ROSTER.iter().any(|(n, _)| n == name) && *name != "SetUp" && *name != "FEN" {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag(value)));
+        }
+    }
+    pgn.push('\n');
+    pgn.push_str(&movetext);
+    pgn.push_str("\n\n");
+    Ok(pgn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_silver_suite() {
+        let games = read_pgn(include_str!("../silversuite.pgn")).unwrap();
+        assert_eq!(games.len(), 50);
+        for game in games.iter() {
+            let (_, moves) = game.replay().unwrap();
+            assert_eq!(Some(moves.len().to_string().as_str()), game.tag("PlyCount"), "game {}", game.number);
+            assert_eq!(game.result, "*");
+        }
+        assert_eq!(games[1].tag("Black"), Some("Reti Opening"));
+    }
+
+    #[test]
+    fn reads_annotations() {
+        let text = "[Event \"Test \\\"quoted\\\"\"]\n[Result \"1-0\"]\n\n\
+                    {Opening} 1. e4 $1 e5!? {Open game} (1... c5 2. Nf3 (2. c3) d6) 2.Nf3 ; line comment\n\
+                    2... Nc6 3. Bb5?! a6 1-0\n\n\
+                    % escaped line\n\
+                    [Event \"No result marker\"]\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4#\n";
+        let games = read_pgn(text).unwrap();
+        assert_eq!(games.len(), 2);
+        let game = &games[0];
+        assert_eq!(game.tag("Event"), Some("Test \"quoted\""));
+        assert_eq!(game.comments, vec!["Opening"]);
+        let sans: Vec<&str> = game.moves.iter().map(|m| m.san.as_str()).collect();
+        assert_eq!(sans, vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+        assert_eq!(game.moves[0].nags, vec![1]);
+        assert_eq!(game.moves[1].nags, vec![5]);
+        assert_eq!(game.moves[1].comments, vec!["Open game"]);
+        assert_eq!(game.moves[1].variations.len(), 1);
+        let variation = &game.moves[1].variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[1].variations[0][0].san, "c3");
+        assert_eq!(game.moves[2].comments, vec!["line comment"]);
+        assert_eq!(game.moves[4].nags, vec![6]);
+        assert_eq!(game.result, "1-0");
+        assert_eq!(games[1].result, "0-1");
+        let (end, _) = games[1].replay().unwrap();
+        assert_eq!(end.status(), crate::GameStatus::Checkmate(crate::Color::Black));
+    }
+
+    #[test]
+    fn reports_errors_by_game_and_ply() {
+        let text = "[Event \"1\"]\n\n1. e4 e5 2. Ke3 *\n\n\
+                    [Event \"2\"]\n\n1. d4 {unterminated\n\n\
+                    [Event \"3\"]\n[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n*\n\n\
+                    [Event \"4\"]\n\n1. c4 ) *\n\n\
+                    [Event \"5\"]\n[Site \"broken]\n[Round \"1\"]\n\n1. e4 *\n\n\
+                    [Event \"6\"]\n\n1. e4 *\n";
+        let games: Vec<Result<PgnGame, PgnError>> = PgnReader::new(text).collect();
+        assert_eq!(games.len(), 6);
+        let err = games[0].as_ref().unwrap().replay().err().unwrap();
+        assert_eq!(err, PgnError { game: 1, ply: 3, kind: PgnErrorKind::Move(String::from("Ke3"), SanError::Illegal) });
+        assert_eq!(err.to_string(), "game 1, ply 3: Ke3: illegal move");
+        assert_eq!(games[1].as_ref().unwrap_err().kind, PgnErrorKind::UnterminatedComment);
+        assert_eq!(games[1].as_ref().unwrap_err().game, 2);
+        assert_eq!(games[1].as_ref().unwrap_err().ply, 1);
+        let err = games[2].as_ref().unwrap().replay().err().unwrap();
+        assert_eq!((err.game, err.ply, err.kind), (3, 0, PgnErrorKind::InvalidFen(FenError::Kings(0))));
+        assert_eq!(games[3].as_ref().unwrap_err().kind, PgnErrorKind::UnbalancedVariation);
+        assert_eq!(games[4].as_ref().unwrap_err().kind, PgnErrorKind::UnterminatedTag);
+        assert_eq!(games[5].as_ref().unwrap().tag("Event"), Some("6"));
+        assert_eq!(read_pgn(text).unwrap_err().game, 2);
+    }
+
+    #[test]
+    fn writes_pgn() {
+        let mut game = Game::from_fen(START_FEN).unwrap();
+        let moves: Vec<Move> = ["f2f3", "e7e5", "g2g4", "d8h4"].iter().map(|m| crate::algebraic_to_move(m).unwrap()).collect();
+        let evals = [None, Some(PgnEval { depth: 6, score: 35, mate_in: 0 }), None,
+                     Some(PgnEval { depth: 2, score: 0, mate_in: 1 })];
+        let pgn = write_pgn(&mut game, &[("White", "rustypawn"), ("ECO", "A00")], &moves, &evals).unwrap();
+        assert_eq!(pgn, "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+                         [White \"rustypawn\"]\n[Black \"?\"]\n[Result \"0-1\"]\n[ECO \"A00\"]\n\n\
+                         1. f3 e5 {[%eval -0.35]} 2. g4 Qh4# {[%eval #-1]} 0-1\n\n");
+        assert_eq!(game.hash(), Game::from_fen(START_FEN).unwrap().hash());
+
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 3 17";
+        let mut game = Game::from_fen(fen).unwrap();
+        let moves: Vec<Move> = ["e8c8", "e1g1", "h3g2"].iter().map(|m| crate::algebraic_to_move(m).unwrap()).collect();
+        let pgn = write_pgn(&mut game, &[], &moves, &[]).unwrap();
+        assert!(pgn.ends_with(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n\n17... O-O-O 18. O-O hxg2 *\n\n", fen)));
+        let read = read_pgn(&pgn).unwrap();
+        assert_eq!(read[0].start_position().unwrap().to_fen(), fen);
+
+        let moves = [crate::algebraic_to_move("e1e2").unwrap()];
+        assert_eq!(write_pgn(&mut game, &[], &moves, &[]).err().unwrap().kind,
+                   PgnErrorKind::Move(String::from("e1e2"), SanError::Illegal));
+    }
+
+    #[test]
+    fn written_games_read_back() {
+        for pgn_game in read_pgn(include_str!("../silversuite.pgn")).unwrap().iter().take(10) {
+            let (_, moves) = pgn_game.replay().unwrap();
+            let mut game = pgn_game.start_position().unwrap();
+            let tags: Vec<(&str, &str)> = pgn_game.tags.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect();
+            let text = write_pgn(&mut game, &tags, &moves, &[]).unwrap();
+            assert!(text.lines().all(|line| line.len() <= 80));
+            let read = read_pgn(&text).unwrap();
+            assert_eq!(read[0].tags, pgn_game.tags);
+            assert_eq!(read[0].moves, pgn_game.moves);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt;
+
+use crate::{Game, Move, Piece};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SanError {
+    Invalid,    // not recognizable as a move
+    Illegal,    // no legal move matches
+    Ambiguous,  // more than one legal move matches
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SanError::Invalid => "invalid move text",
+            SanError::Illegal => "illegal move",
+            SanError::Ambiguous => "ambiguous move",
+        })
+    }
+}
+
+fn piece_letter(piece: Piece) -> char {
+    match piece {
+        Piece::Pawn => 'P',
+        Piece::Bishop => 'B',
+        Piece::Knight => 'N',
+        Piece::Rook => 'R',
+        Piece::Queen => 'Q',
+        Piece::King => 'K',
+    }
+}
+
+fn letter_piece(c: char) -> Option<Piece> {
+    match c {
+        'P' => Some(Piece::Pawn),
+        'B' => Some(Piece::Bishop),
+        'N' | 'n' => Some(Piece::Knight),
+        'R' | 'r' => Some(Piece::Rook),
+        'Q' | 'q' => Some(Piece::Queen),
+        'K' | 'k' => Some(Piece::King),
+        _ => None
+    }
+}
+
+impl Game {
+
+    fn moving_piece(self: &Game, mv: Move) -> Piece {
+        self.piece_at(mv.from()).map(|(_, p)| p).unwrap_or(Piece::Pawn)
+    }
+
+    fn is_castling(self: &Game, mv: Move) -> bool {
+        self.moving_piece(mv) == Piece::King && (mv.from().file() as isize - mv.to().file() as isize).abs() == 2
+    }
+
+    // Standard Algebraic Notation for a legal move, including check and mate
+    // markers.
+    pub fn move_to_san(self: &mut Game, mv: Move) -> String {
+        let mut san = String::new();
+        let piece = self.moving_piece(mv);
+
+        if self.is_castling(mv) {
+            san.push_str(if mv.to().file() == 6 { "O-O" } else { "O-O-O" });
+        } else {
+            let capture = self.piece_at(mv.to()).is_some()
+                || (piece == Piece::Pawn && mv.from().file() != mv.to().file());
+            if piece == Piece::Pawn {
+                if capture {
+                    san.push((b'a' + mv.from().file() as u8) as char);
+                }
+            } else {
+                san.push(piece_letter(piece));
+                let rivals: Vec<Move> = self.legal_moves().into_iter()
+                    .filter(|m| m.to() == mv.to() && m.from() != mv.from() && self.moving_piece(*m) == piece)
+                    .collect();
+                if !rivals.is_empty() {
+                    let from = mv.from().to_string();
+                    if rivals.iter().all(|m| m.from().file() != mv.from().file()) {
+                        san.push_str(&from[0..1]);
+                    } else if rivals.iter().all(|m| m.from().rank() != mv.from().rank()) {
+                        san.push_str(&from[1..2]);
+                    } else {
+                        san.push_str(&from);
+                    }
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&mv.to().to_string());
+            if let Some(promotion) = mv.promotion() {
+                san.push('=');
+                san.push(piece_letter(promotion));
+            }
+        }
+
+        if self.make_move(mv) {
+            if self.in_check() {
+                san.push(if self.legal_moves().is_empty() { '#' } else { '+' });
+            }
+            self.unmake_move(mv);
+        }
+        san
+    }
+
+    // Accepts SAN with common deviations: missing or superfluous capture
+    // marks, check and annotation suffixes, zeros in castling, promotion
+    // without '=', lowercase piece letters (except b) and long algebraic or
+    // coordinate notation.
+    pub fn parse_san(self: &Game, text: &str) -> Result<Move, SanError> {
+        let text = text.trim_end_matches(['+', '#', '!', '?'].as_ref());
+        let legal = self.legal_moves();
+
+        if let Some(mv) = legal.iter().find(|m| m.to_string() == text) {
+            return Ok(*mv);
+        }
+
+        let castling_file = match text {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None
+        };
+        if let Some(file) = castling_file {
+            return legal.iter().find(|m| self.is_castling(**m) && m.to().file() == file)
+                .copied().ok_or(SanError::Illegal);
+        }
+
+        let mut chars: Vec<char> = text.chars().filter(|&c| c != 'x' && c != '-' && c != ':').collect();
+
+        let mut promotion = None;
+        if let Some(&c) = chars.last() {
+            if let Some(p) = letter_piece(c).or(if c == 'b' { Some(Piece::Bishop) } else { None }) {
+                promotion = Some(p);
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+        }
+        if chars.len() < 2 {
+            return Err(SanError::Invalid);
+        }
+        let to_text: String = chars[chars.len() - 2..].iter().collect();
+        let to = crate::Square::from_algebraic(&to_text).ok_or(SanError::Invalid)?;
+        chars.truncate(chars.len() - 2);
+
+        let mut piece = Piece::Pawn;
+        if let Some(&c) = chars.first() {
+            if let Some(p) = letter_piece(c) {
+                piece = p;
+                chars.remove(0);
+            }
+        }
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in chars {
+            match c {
+                'a'..='h' => from_file = Some(c as usize - 'a' as usize),
+                '1'..='8' => from_rank = Some(c as usize - '1' as usize),
+                _ => return Err(SanError::Invalid)
+            }
+        }
+
+        let mut candidates = legal.iter().filter(|m| {
+            m.to() == to && m.promotion() == promotion && self.moving_piece(**m) == piece
+                && from_file.is_none_or(|f| m.from().file() == f)
+                && from_rank.is_none_or(|r| m.from().rank() == r)
+        });
+        match (candidates.next(), candidates.next()) {
+            (Some(mv), None) => Ok(*mv),
+            (None, _) => Err(SanError::Illegal),
+            _ => Err(SanError::Ambiguous)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san_sequence(fen: &str, moves: &[&str]) -> Vec<String> {
+        let mut game = Game::from_fen(fen).unwrap();
+        let mut result = Vec::new();
+        for text in moves.iter() {
+            let mv = crate::algebraic_to_move(text).unwrap();
+            result.push(game.move_to_san(mv));
+            game.make_move(mv);
+        }
+        result
+    }
+
+    #[test]
+    fn formats_san() {
+        assert_eq!(san_sequence("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0",
+                                &["f2f3", "e7e5", "g2g4", "d8h4"]),
+                   vec!["f3", "e5", "g4", "Qh4#"]);
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0";
+        assert_eq!(san_sequence(kiwipete, &["e1g1", "e8c8"]), vec!["O-O", "O-O-O"]);
+        assert_eq!(san_sequence(kiwipete, &["d5e6", "h3g2"]), vec!["dxe6", "hxg2"]);
+        assert_eq!(san_sequence(kiwipete, &["e2a6", "b4c3"]), vec!["Bxa6", "bxc3"]);
+        assert_eq!(san_sequence("4k3/8/8/8/8/8/8/R4RK1 w - - 0", &["a1d1"]), vec!["Rad1"]);
+        assert_eq!(san_sequence("4k3/8/8/8/R7/8/8/R3K3 w - - 0", &["a1a2"]), vec!["R1a2"]);
+        assert_eq!(san_sequence("7k/8/8/8/Q1Q5/8/Q7/4K3 w - - 0", &["a4b3"]), vec!["Qa4b3"]);
+        assert_eq!(san_sequence("7k/1P6/8/8/8/8/8/4K3 w - - 0", &["b7b8q"]), vec!["b8=Q+"]);
+        assert_eq!(san_sequence("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0", &["e4d3"]), vec!["exd3"]);
+    }
+
+    #[test]
+    fn parses_san() {
+        let game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0").unwrap();
+        let parse = |s: &str| game.parse_san(s).map(|m| m.to_string());
+        assert_eq!(parse("O-O"), Ok(String::from("e1g1")));
+        assert_eq!(parse("0-0-0+"), Ok(String::from("e1c1")));
+        assert_eq!(parse("Nxf7!?"), Ok(String::from("e5f7")));
+        assert_eq!(parse("Nf7"), Ok(String::from("e5f7")));
+        assert_eq!(parse("Ne5-f7"), Ok(String::from("e5f7")));
+        assert_eq!(parse("dxe6"), Ok(String::from("d5e6")));
+        assert_eq!(parse("Nb5"), Ok(String::from("c3b5")));
+        assert_eq!(parse("e2a6"), Ok(String::from("e2a6")));
+        assert_eq!(parse("Rb1"), Ok(String::from("a1b1")));
+        assert_eq!(parse("Nd5"), Err(SanError::Illegal));
+        assert_eq!(parse("Qe5"), Err(SanError::Illegal));
+        assert_eq!(parse("Z9"), Err(SanError::Invalid));
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/R4RK1 w - - 0").unwrap();
+        assert_eq!(game.parse_san("Rd1"), Err(SanError::Ambiguous));
+        let game = Game::from_fen("8/1P5k/8/8/8/8/8/4K3 w - - 0").unwrap();
+        assert_eq!(game.parse_san("b8=Q").map(|m| m.to_string()), Ok(String::from("b7b8q")));
+        assert_eq!(game.parse_san("b8N").map(|m| m.to_string()), Ok(String::from("b7b8n")));
+        assert_eq!(game.parse_san("b8"), Err(SanError::Illegal));
+    }
+
+    #[test]
+    fn san_round_trip() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        for fen in fens.iter() {
+            let mut game = Game::from_fen(fen).unwrap();
+            for mv in game.legal_moves() {
+                let san = game.move_to_san(mv);
+                assert_eq!(game.parse_san(&san), Ok(mv), "{} in {}", san, fen);
+            }
+        }
+    }
+}
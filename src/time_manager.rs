@@ -0,0 +1,106 @@
+use crate::Color;
+
+// Time kept in reserve for communication delays, in milliseconds.
+const MOVE_OVERHEAD: u64 = 30;
+// Moves assumed to remain when the time control does not say.
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+// The hard limit may exceed the soft limit by this factor ...
+const HARD_FACTOR: u64 = 4;
+// ... but never use more than this fraction (1/n) of the remaining time.
+const HARD_FRACTION: u64 = 3;
+// The search time when the go command gives neither a clock for the side
+// to move nor a move time.
+const DEFAULT_MOVE_TIME: u64 = 5000;
+
+// The clock situation from a UCI go command, in milliseconds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct TimeControl {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: u64,
+    pub binc: u64,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+}
+
+// The search does not start a new iteration after the soft limit and is
+// stopped at the hard limit, both in milliseconds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TimeLimits {
+    pub soft: u64,
+    pub hard: u64,
+}
+
+impl TimeLimits {
+
+    pub const INFINITE: TimeLimits = TimeLimits { soft: u64::MAX, hard: u64::MAX };
+
+    pub fn movetime(millis: u64) -> TimeLimits {
+        TimeLimits { soft: millis, hard: millis }
+    }
+
+    // Limits for the side to move. Without any clock information a fixed
+    // default time is used; only 'go infinite' searches without a limit.
+    pub fn new(tc: &TimeControl, side: Color) -> TimeLimits {
+        if let Some(movetime) = tc.movetime {
+            return TimeLimits::movetime(movetime.saturating_sub(MOVE_OVERHEAD).max(1));
+        }
+        let (time, inc) = match side {
+            Color::White => (tc.wtime, tc.winc),
+            Color::Black => (tc.btime, tc.binc),
+        };
+        let time = match time {
+            Some(t) => t,
+            None => return TimeLimits::movetime(DEFAULT_MOVE_TIME),
+        };
+        let available = time.saturating_sub(MOVE_OVERHEAD).max(1);
+        let moves_to_go = tc.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let hard_cap = if moves_to_go == 1 { available } else { (available / HARD_FRACTION).max(1) };
+        let soft = (time / moves_to_go + inc * 3 / 4).min(hard_cap);
+        let hard = soft.saturating_mul(HARD_FACTOR).min(hard_cap);
+        TimeLimits { soft: soft.max(1), hard: hard.max(1) }
+    }
+
+    // Whether an iteration expected to take `estimate` milliseconds, starting
+    // after `elapsed`, is likely to finish within the soft limit.
+    pub fn allows_iteration(self: &TimeLimits, elapsed: u64, estimate: u64) -> bool {
+        elapsed.saturating_add(estimate) <= self.soft
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_limits() {
+        let sudden_death = TimeControl { wtime: Some(60000), btime: Some(1000), ..TimeControl::default() };
+        assert_eq!(TimeLimits::new(&sudden_death, Color::White), TimeLimits { soft: 2000, hard: 8000 });
+        assert_eq!(TimeLimits::new(&sudden_death, Color::Black), TimeLimits { soft: 33, hard: 132 });
+
+        let increment = TimeControl { wtime: Some(60000), winc: 2000, btime: Some(100), binc: 2000, ..TimeControl::default() };
+        assert_eq!(TimeLimits::new(&increment, Color::White), TimeLimits { soft: 3500, hard: 14000 });
+        // the increment cannot be spent before it is received
+        assert_eq!(TimeLimits::new(&increment, Color::Black), TimeLimits { soft: 23, hard: 23 });
+
+        let last_move = TimeControl { wtime: Some(5000), movestogo: Some(1), ..TimeControl::default() };
+        assert_eq!(TimeLimits::new(&last_move, Color::White), TimeLimits { soft: 4970, hard: 4970 });
+
+        let movetime = TimeControl { movetime: Some(1000), wtime: Some(60000), ..TimeControl::default() };
+        assert_eq!(TimeLimits::new(&movetime, Color::White), TimeLimits::movetime(970));
+        assert_eq!(TimeLimits::new(&TimeControl::default(), Color::White), TimeLimits::movetime(DEFAULT_MOVE_TIME));
+        // only the clock of the side to move counts
+        assert_eq!(TimeLimits::new(&TimeControl { wtime: Some(60000), ..TimeControl::default() }, Color::Black),
+                   TimeLimits::movetime(DEFAULT_MOVE_TIME));
+        assert_eq!(TimeLimits::new(&TimeControl { btime: Some(0), ..TimeControl::default() }, Color::Black),
+                   TimeLimits { soft: 1, hard: 1 });
+    }
+
+    #[test]
+    fn stops_iterating_at_soft_limit() {
+        let limits = TimeLimits { soft: 1000, hard: 4000 };
+        assert!(limits.allows_iteration(300, 600));
+        assert!(!limits.allows_iteration(300, 800));
+        assert!(TimeLimits::INFINITE.allows_iteration(u64::MAX - 1, 10));
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{Move, DUMMY_MOVE, MATE_VALUE, MAX_DEPTH};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+// Shared by the search threads without locking. The key is stored xor'ed
+// with the data, so an entry torn by concurrent writes fails the key check
+// instead of returning another position's data.
+struct Entry {
+    key: AtomicU64,  // hash ^ data
+    data: AtomicU64,  // score << 40 | bound << 32 | depth << 24 | move
+}
+
+impl Entry {
+    fn empty() -> Entry {
+        Entry { key: AtomicU64::new(0), data: AtomicU64::new(0) }
+    }
+}
+
+pub struct TtEntry {
+    pub mv: Move,
+    pub score: isize,
+    pub depth: usize,
+    pub bound: Bound,
+}
+
+impl TtEntry {
+    // Score to return without searching, if the stored bound settles the
+    // node for the window (alpha, beta).
+    pub fn cutoff(self: &TtEntry, alpha: isize, beta: isize) -> Option<isize> {
+        match self.bound {
+            Bound::Exact => Some(std::cmp::min(std::cmp::max(self.score, alpha), beta)),
+            Bound::Lower if self.score >= beta => Some(beta),
+            Bound::Upper if self.score <= alpha => Some(alpha),
+            _ => None,
+        }
+    }
+}
+
+// Mate scores are stored relative to the node they were found in (distance
+// to mate from there) and converted back to root-relative when probed.
+fn score_to_tt(score: isize, ply: usize) -> isize {
+    if score >= MATE_VALUE - MAX_DEPTH as isize {
+        score + ply as isize
+    } else if score <= -(MATE_VALUE - MAX_DEPTH as isize) {
+        score - ply as isize
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: isize, ply: usize) -> isize {
+    if score >= MATE_VALUE - MAX_DEPTH as isize {
+        score - ply as isize
+    } else if score <= -(MATE_VALUE - MAX_DEPTH as isize) {
+        score + ply as isize
+    } else {
+        score
+    }
+}
+
+pub struct TranspositionTable {
+    entries: Vec<Entry>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let mut tt = TranspositionTable { entries: Vec::new(), mask: 0 };
+        tt.resize(megabytes);
+        tt
+    }
+
+    // The number of entries is rounded down to a power of two.
+    pub fn resize(self: &mut TranspositionTable, megabytes: usize) {
+        let max_entries = std::cmp::max(1, (megabytes << 20) / std::mem::size_of::<Entry>());
+        let count = 1usize << (63 - (max_entries as u64).leading_zeros());
+        self.entries = (0..count).map(|_| Entry::empty()).collect();
+        self.mask = count - 1;
+    }
+
+    pub fn clear(self: &mut TranspositionTable) {
+        for entry in self.entries.iter_mut() {
+            *entry = Entry::empty();
+        }
+    }
+
+    pub fn probe(self: &TranspositionTable, hash: u64, ply: usize) -> Option<TtEntry> {
+        let entry = &self.entries[hash as usize & self.mask];
+        let data = entry.data.load(Ordering::Relaxed);
+        if data == 0 || entry.key.load(Ordering::Relaxed) ^ data != hash {
+            return None;
+        }
+        Some(TtEntry {
+            mv: Move((data & 0xffffff) as u32),
+            depth: ((data >> 24) & 0xff) as usize,
+            bound: match (data >> 32) & 0xff {
+                1 => Bound::Exact,
+                2 => Bound::Lower,
+                _ => Bound::Upper,
+            },
+            score: score_from_tt((data as i64 >> 40) as isize, ply),
+        })
+    }
+
+    pub fn store(self: &TranspositionTable, hash: u64, ply: usize, depth: usize,
+                 score: isize, bound: Bound, mv: Move) {
+        let entry = &self.entries[hash as usize & self.mask];
+        let old_data = entry.data.load(Ordering::Relaxed);
+        let mut mv = mv.0 as u64;
+        if old_data != 0 && entry.key.load(Ordering::Relaxed) ^ old_data == hash {
+            if ((old_data >> 24) & 0xff) as usize > depth && bound != Bound::Exact {
+                return;
+            }
+            if mv == DUMMY_MOVE.0 as u64 {
+                mv = old_data & 0xffffff;
+            }
+        }
+        let bound_bits: u64 = match bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        let score = score_to_tt(score, ply) as i64;
+        let data = (score << 40) as u64 | bound_bits << 32 | (std::cmp::min(depth, 0xff) as u64) << 24 | mv;
+        entry.key.store(hash ^ data, Ordering::Relaxed);
+        entry.data.store(data, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mate_scores_are_ply_relative() {
+        let tt = TranspositionTable::new(1);
+        // mate found 3 plies below a node at ply 5 ...
+        tt.store(12345, 5, 4, MATE_VALUE - 8, Bound::Exact, Move(0x1234));
+        // ... is mate 3 plies below the same node reached at ply 2
+        let entry = tt.probe(12345, 2).unwrap();
+        assert_eq!(entry.score, MATE_VALUE - 5);
+        assert_eq!(entry.mv, Move(0x1234));
+        assert_eq!(entry.depth, 4);
+        assert_eq!(entry.bound, Bound::Exact);
+
+        tt.store(999, 3, 2, -(MATE_VALUE - 7), Bound::Upper, DUMMY_MOVE);
+        let entry = tt.probe(999, 1).unwrap();
+        assert_eq!(entry.score, -(MATE_VALUE - 5));
+        assert_eq!(entry.bound, Bound::Upper);
+    }
+
+    #[test]
+    fn keeps_move_when_storing_without_one() {
+        let tt = TranspositionTable::new(1);
+        tt.store(42, 0, 3, 17, Bound::Lower, Move(0x4321));
+        tt.store(42, 0, 4, -30, Bound::Upper, DUMMY_MOVE);
+        let entry = tt.probe(42, 0).unwrap();
+        assert_eq!(entry.mv, Move(0x4321));
+        assert_eq!(entry.score, -30);
+        assert!(tt.probe(43, 0).is_none());
+    }
+}